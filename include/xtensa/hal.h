@@ -0,0 +1,43 @@
+/*
+ * xtensa/hal.h -- subset of the Xtensa HAL interface
+ *
+ * Only the hardware version constants, which core-isa.h compares against
+ * (e.g. in the definition of XCHAL_USE_MEMCTL), are provided here.
+ */
+
+/*
+ * Copyright (c) 2005-2014 Cadence Design Systems, Inc.
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining
+ * a copy of this software and associated documentation files (the
+ * "Software"), to deal in the Software without restriction, including
+ * without limitation the rights to use, copy, modify, merge, publish,
+ * distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so, subject to
+ * the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included
+ * in all copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+ * EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+ * MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+ * IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+ * CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+ * TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+#ifndef XTENSA_HAL_H
+#define XTENSA_HAL_H
+
+/*  Hardware release identifiers (for XCHAL_HW_VERSION etc.):  */
+#define XTENSA_HWVERSION_RA_2004_1	210001	/* versions LX1.0.x */
+#define XTENSA_HWVERSION_RB_2006_0	220000	/* versions LX2.0.x */
+#define XTENSA_HWVERSION_RC_2009_0	230000	/* versions LX3.0.x */
+#define XTENSA_HWVERSION_RD_2010_0	240000	/* versions LX4.0.x */
+#define XTENSA_HWVERSION_RE_2012_0	250000	/* versions LX5.0.x */
+#define XTENSA_HWVERSION_RF_2014_0	260000	/* versions LX6.0.x */
+#define XTENSA_HWVERSION_RG_2015_0	270000	/* versions LX7.0.x */
+
+#endif /* XTENSA_HAL_H */
@@ -0,0 +1,181 @@
+//! A typed model of the caches described by the `XCHAL_ICACHE_*` and
+//! `XCHAL_DCACHE_*` definitions.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use strum_macros::{Display, EnumIter};
+
+use crate::{flag, integer, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, Serialize)]
+pub enum CacheKind {
+    #[strum(to_string = "ICACHE")]
+    Instruction,
+    #[strum(to_string = "DCACHE")]
+    Data,
+}
+
+/// The configuration of a single cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct CacheConfig {
+    pub kind: CacheKind,
+    /// The size of the cache, in bytes.
+    pub size: u32,
+    /// The size of each line, in bytes.
+    pub line_size: u32,
+    /// The associativity of the cache.
+    pub ways: u32,
+    /// The number of sets, each holding one line per way.
+    pub sets: u32,
+    /// Whether the cache is write-back rather than write-through. This is
+    /// always false for the instruction cache.
+    pub writeback: bool,
+    /// Whether individual lines can be locked.
+    pub line_lockable: bool,
+    /// Whether the cache is kept coherent with other cores. This is always
+    /// false for the instruction cache.
+    pub coherent: bool,
+    /// Whether ways can be enabled and disabled at runtime.
+    pub dynamic_ways: bool,
+}
+
+impl CacheConfig {
+    /// Build the configuration of the given cache from the
+    /// `XCHAL_<kind>_*` definitions, where `<kind>` is either `ICACHE` or
+    /// `DCACHE`.
+    ///
+    /// Returns `None` if the chip does not have the cache, in which case its
+    /// size is zero.
+    pub fn from_defines(defines: &HashMap<String, Value>, kind: CacheKind) -> Result<Option<Self>> {
+        let size = integer(defines, &format!("XCHAL_{}_SIZE", kind))?;
+        if size == 0 {
+            return Ok(None);
+        }
+
+        let line_size = integer(defines, &format!("XCHAL_{}_LINESIZE", kind))?;
+        let ways = integer(defines, &format!("XCHAL_{}_WAYS", kind))?;
+        let set_width = integer(defines, &format!("XCHAL_{}_SETWIDTH", kind))?;
+        if !(0..32).contains(&set_width) {
+            bail!("Unsupported {} set width: {}", kind, set_width);
+        }
+
+        let (writeback, coherent) = match kind {
+            CacheKind::Instruction => (false, false),
+            CacheKind::Data => (
+                flag(defines, "XCHAL_DCACHE_IS_WRITEBACK")?,
+                flag(defines, "XCHAL_DCACHE_IS_COHERENT")?,
+            ),
+        };
+
+        let cache = Self {
+            kind,
+            size: size as u32,
+            line_size: line_size as u32,
+            ways: ways as u32,
+            sets: 1 << set_width,
+            writeback,
+            line_lockable: flag(defines, &format!("XCHAL_{}_LINE_LOCKABLE", kind))?,
+            coherent,
+            dynamic_ways: flag(defines, &format!("XCHAL_HAVE_{}_DYN_WAYS", kind))?,
+        };
+        cache.check()?;
+
+        Ok(Some(cache))
+    }
+
+    /// Check that the geometry of the cache is consistent, i.e. that its size
+    /// is the product of its line size, ways and sets.
+    pub fn check(&self) -> Result<()> {
+        let expected = self.line_size as u64 * self.ways as u64 * self.sets as u64;
+        if self.size as u64 != expected {
+            bail!(
+                "Inconsistent {} geometry: size is {}, but {} ways of {} sets of {} byte lines is {}",
+                self.kind,
+                self.size,
+                self.ways,
+                self.sets,
+                self.line_size,
+                expected
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The size of each way, in bytes.
+    pub fn way_size(&self) -> u32 {
+        self.line_size * self.sets
+    }
+}
+
+/// The caches of a chip, either of which may be absent.
+///
+/// ```no_run
+/// use xtensa_core_isa::{parse_defines, Caches, Chip};
+///
+/// let caches = Caches::from_defines(&parse_defines(Chip::Esp32s3)?)?;
+/// if let Some(dcache) = caches.data {
+///     println!("{} bytes, {} ways", dcache.size, dcache.ways);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Caches {
+    pub instruction: Option<CacheConfig>,
+    pub data: Option<CacheConfig>,
+}
+
+impl Caches {
+    pub fn from_defines(defines: &HashMap<String, Value>) -> Result<Self> {
+        Ok(Self {
+            instruction: CacheConfig::from_defines(defines, CacheKind::Instruction)?,
+            data: CacheConfig::from_defines(defines, CacheKind::Data)?,
+        })
+    }
+
+    /// The caches which are present.
+    pub fn iter(&self) -> impl Iterator<Item = &CacheConfig> {
+        self.instruction.iter().chain(self.data.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    const HEADER: &str = "
+        #define XCHAL_ICACHE_SIZE 16384
+        #define XCHAL_ICACHE_LINESIZE 32
+        #define XCHAL_ICACHE_WAYS 4
+        #define XCHAL_ICACHE_SETWIDTH 7
+        #define XCHAL_ICACHE_LINE_LOCKABLE 1
+        #define XCHAL_DCACHE_SIZE 0
+    ";
+
+    #[test]
+    fn geometry() {
+        let caches = Caches::from_defines(&parse_str_defines(HEADER).unwrap()).unwrap();
+        let icache = caches.instruction.unwrap();
+
+        assert_eq!(icache.sets, 128);
+        assert_eq!(icache.way_size(), 4096);
+        assert!(icache.line_lockable);
+        assert!(!icache.dynamic_ways);
+        assert_eq!(caches.data, None);
+        assert_eq!(caches.iter().count(), 1);
+    }
+
+    #[test]
+    fn inconsistent_geometry() {
+        let header = HEADER.replace("XCHAL_ICACHE_WAYS 4", "XCHAL_ICACHE_WAYS 2");
+        let err = Caches::from_defines(&parse_str_defines(&header).unwrap()).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Inconsistent ICACHE geometry: size is 16384, but 2 ways of 128 sets of 32 byte lines is 8192"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! The parsed definitions of a core configuration, along with where they were
+//! defined.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::Index,
+    path::PathBuf,
+};
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::{Diagnostic, Value};
+
+/// The location of a definition within a header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Location {
+    /// The path of the header, or `None` if it was parsed from a string.
+    pub path: Option<PathBuf>,
+    /// The (1-based) line number on which the definition starts.
+    pub line: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}", path.display(), self.line),
+            None => write!(f, "<string>:{}", self.line),
+        }
+    }
+}
+
+/// A single parsed definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub identifier: String,
+    /// The replacement text of the definition, as written in the header.
+    pub raw: String,
+    /// The resolved value of the definition.
+    pub value: Value,
+    pub location: Location,
+    /// The macros which were expanded in resolving the value, such as
+    /// `XCHAL_INTLEVEL6_VECOFS` or `XTENSA_HWVERSION_RE_2012_0`, in the order
+    /// in which each was first expanded. Function-like macros are included,
+    /// with the replacement text of their definition.
+    pub resolved_through: Vec<ResolutionStep>,
+}
+
+/// A macro through which a definition was resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolutionStep {
+    pub identifier: String,
+    /// The replacement text of the macro, as written in the header, excluding
+    /// the parameter list of a function-like macro.
+    pub raw: String,
+    /// Where the macro was defined, unless it was predefined.
+    pub location: Option<Location>,
+}
+
+/// The result of querying a single definition.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Resolution {
+    pub identifier: String,
+    pub value: Value,
+    /// The definition itself, followed by each macro through which it was
+    /// resolved, whether object-like or function-like.
+    pub trace: Vec<ResolutionStep>,
+}
+
+/// The definitions parsed from a core configuration header, along with any
+/// headers it includes.
+///
+/// This is serialized as a map from each identifier to its resolved value,
+/// ordered by identifier.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoreConfig {
+    definitions: BTreeMap<String, Definition>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+impl CoreConfig {
+    pub(crate) fn insert(&mut self, definition: Definition) {
+        self.definitions
+            .insert(definition.identifier.clone(), definition);
+    }
+
+    /// The definition of the given identifier.
+    pub fn get(&self, identifier: &str) -> Option<&Definition> {
+        self.definitions.get(identifier)
+    }
+
+    /// The resolved value of the given identifier.
+    pub fn value(&self, identifier: &str) -> Option<&Value> {
+        self.get(identifier).map(|definition| &definition.value)
+    }
+
+    /// Query the resolved value of the given identifier, along with the chain
+    /// of macros through which it was resolved.
+    ///
+    /// Fails if there is no such definition, including the reason if it could
+    /// not be resolved.
+    ///
+    /// ```
+    /// use xtensa_core_isa::Parser;
+    ///
+    /// let config = Parser::new().parse_str("#define A 0x10\n#define B (A)\n#define C B")?;
+    /// let resolution = config.query("C")?;
+    /// assert_eq!(resolution.value.as_integer(), Some(&16));
+    /// assert_eq!(
+    ///     resolution.trace.iter().map(|step| step.identifier.as_str()).collect::<Vec<_>>(),
+    ///     ["C", "B", "A"]
+    /// );
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn query(&self, identifier: &str) -> anyhow::Result<Resolution> {
+        let definition = match self.get(identifier) {
+            Some(definition) => definition,
+            None => match self
+                .diagnostics
+                .iter()
+                .find(|diagnostic| diagnostic.identifier == identifier)
+            {
+                Some(diagnostic) => {
+                    anyhow::bail!("Unable to resolve {}: {}", identifier, diagnostic.reason)
+                }
+                None => anyhow::bail!("No definition of {}", identifier),
+            },
+        };
+
+        let mut trace = vec![ResolutionStep {
+            identifier: definition.identifier.clone(),
+            raw: definition.raw.clone(),
+            location: Some(definition.location.clone()),
+        }];
+        trace.extend(definition.resolved_through.iter().cloned());
+
+        Ok(Resolution {
+            identifier: definition.identifier.clone(),
+            value: definition.value.clone(),
+            trace,
+        })
+    }
+
+    /// All definitions, ordered by identifier.
+    pub fn iter(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// The diagnostics reported while parsing, in the order in which they were
+    /// encountered.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The resolved values of all definitions, keyed by identifier.
+    pub fn to_map(&self) -> HashMap<String, Value> {
+        self.iter()
+            .map(|definition| (definition.identifier.clone(), definition.value.clone()))
+            .collect()
+    }
+}
+
+impl Index<&str> for CoreConfig {
+    type Output = Value;
+
+    fn index(&self, identifier: &str) -> &Value {
+        self.value(identifier)
+            .unwrap_or_else(|| panic!("No definition of {}", identifier))
+    }
+}
+
+impl Serialize for CoreConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for definition in self.iter() {
+            map.serialize_entry(&definition.identifier, &definition.value)?;
+        }
+
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Parser;
+
+    #[test]
+    fn trace_includes_function_like_macros() {
+        let config = Parser::new()
+            .parse_str("#define SIZE 4\n#define KB(x) ((x) * 1024)\n#define CACHE KB(SIZE)")
+            .unwrap();
+        let resolution = config.query("CACHE").unwrap();
+        assert_eq!(resolution.value.as_integer(), Some(&4096));
+
+        let trace = resolution
+            .trace
+            .iter()
+            .map(|step| (step.identifier.as_str(), step.raw.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trace,
+            [("CACHE", "KB(SIZE)"), ("KB", "((x) * 1024)"), ("SIZE", "4")]
+        );
+    }
+}
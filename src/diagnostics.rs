@@ -0,0 +1,77 @@
+//! Diagnostics reported while parsing, in place of failing outright.
+
+use std::fmt;
+
+use strum_macros::Display;
+
+use crate::Location;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticKind {
+    /// A definition without any replacement text, other than an include
+    /// guard. These are valid, but have no value.
+    EmptyDefinition,
+    /// A macro which was redefined with different replacement text.
+    Redefinition,
+    /// A definition whose replacement text could not be evaluated.
+    UnresolvedDefinition,
+    /// A definition which could not be tokenized, or whose parameter list or
+    /// use of `##` is invalid. The definition is ignored.
+    MalformedDefinition,
+    /// The include guard of the header being parsed was already defined, such
+    /// as by a header included from a prelude. The guard is ignored so that
+    /// the header is processed regardless.
+    DefinedIncludeGuard,
+}
+
+impl DiagnosticKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticKind::EmptyDefinition
+            | DiagnosticKind::Redefinition
+            | DiagnosticKind::DefinedIncludeGuard => Severity::Warning,
+            DiagnosticKind::UnresolvedDefinition | DiagnosticKind::MalformedDefinition => {
+                Severity::Error
+            }
+        }
+    }
+}
+
+/// A problem encountered with a definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// The identifier of the definition, which is empty if the directive was
+    /// too malformed to name one.
+    pub identifier: String,
+    pub location: Option<Location>,
+    pub reason: String,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+
+        write!(f, "{}: ", self.severity())?;
+        if !self.identifier.is_empty() {
+            write!(f, "{}: ", self.identifier)?;
+        }
+
+        write!(f, "{}", self.reason)
+    }
+}
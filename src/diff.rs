@@ -0,0 +1,335 @@
+//! Comparison of the definitions of two configurations.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+};
+
+use serde::Serialize;
+use strum_macros::{Display, EnumIter};
+
+use crate::Value;
+
+/// A category of definitions, determined from their identifiers.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumIter, Serialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Interrupts,
+    Caches,
+    Memory,
+    Vectors,
+    Features,
+    Other,
+}
+
+impl Category {
+    /// The category of the definition with the given identifier.
+    pub fn of(identifier: &str) -> Self {
+        let name = identifier.strip_prefix("XCHAL_").unwrap_or(identifier);
+        let starts_with = |prefixes: &[&str]| prefixes.iter().any(|p| name.starts_with(p));
+
+        if name.starts_with("HAVE_") {
+            Category::Features
+        } else if name.contains("_VECOFS") || name.contains("VECTOR") || name.contains("VECBASE") {
+            Category::Vectors
+        } else if starts_with(&[
+            "INT",
+            "NUM_INTERRUPTS",
+            "NUM_INTLEVELS",
+            "EXTINT",
+            "TIMER",
+            "NUM_TIMERS",
+            "EXCM_LEVEL",
+            "NMILEVEL",
+            "DEBUGLEVEL",
+        ]) {
+            Category::Interrupts
+        } else if starts_with(&["ICACHE_", "DCACHE_", "CACHE_"]) {
+            Category::Caches
+        } else if starts_with(&[
+            "INSTROM",
+            "INSTRAM",
+            "DATAROM",
+            "DATARAM",
+            "URAM",
+            "XLMI",
+            "NUM_INSTROM",
+            "NUM_INSTRAM",
+            "NUM_DATAROM",
+            "NUM_DATARAM",
+            "NUM_URAM",
+            "NUM_XLMI",
+        ]) {
+            Category::Memory
+        } else {
+            Category::Other
+        }
+    }
+}
+
+/// A definition whose value differs between two configurations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangedValue {
+    pub first: Value,
+    pub second: Value,
+}
+
+/// The differences within a single category, keyed by identifier.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CategoryDiff {
+    /// The definitions only present in the second configuration.
+    pub added: BTreeMap<String, Value>,
+    /// The definitions only present in the first configuration.
+    pub removed: BTreeMap<String, Value>,
+    /// The definitions present in both configurations, with different values.
+    pub changed: BTreeMap<String, ChangedValue>,
+}
+
+impl CategoryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The differences between the definitions of two configurations, grouped by
+/// [Category]. Categories without any differences are omitted.
+///
+/// ```
+/// use xtensa_core_isa::{parse_str_defines, Category, Diff};
+///
+/// let first = parse_str_defines("#define XCHAL_HAVE_FP 1\n#define XCHAL_ICACHE_SIZE 16384")?;
+/// let second = parse_str_defines("#define XCHAL_HAVE_FP 0\n#define XCHAL_ICACHE_SIZE 16384")?;
+/// let diff = Diff::new(&first, &second);
+/// assert!(diff.categories[&Category::Features].changed.contains_key("XCHAL_HAVE_FP"));
+/// assert!(!diff.categories.contains_key(&Category::Caches));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Diff {
+    pub categories: BTreeMap<Category, CategoryDiff>,
+}
+
+impl Diff {
+    /// Compare the definitions of a first configuration with those of a
+    /// second.
+    pub fn new(first: &HashMap<String, Value>, second: &HashMap<String, Value>) -> Self {
+        let mut categories: BTreeMap<Category, CategoryDiff> = BTreeMap::new();
+
+        for (identifier, value) in first {
+            let category = categories.entry(Category::of(identifier)).or_default();
+            match second.get(identifier) {
+                None => {
+                    category.removed.insert(identifier.clone(), value.clone());
+                }
+                Some(other) if other != value => {
+                    category.changed.insert(
+                        identifier.clone(),
+                        ChangedValue {
+                            first: value.clone(),
+                            second: other.clone(),
+                        },
+                    );
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (identifier, value) in second {
+            if !first.contains_key(identifier) {
+                categories
+                    .entry(Category::of(identifier))
+                    .or_default()
+                    .added
+                    .insert(identifier.clone(), value.clone());
+            }
+        }
+
+        categories.retain(|_, diff| !diff.is_empty());
+
+        Self { categories }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty()
+    }
+
+    /// Format the differences as Markdown, with a section per category
+    /// containing a table of the values in each configuration, whose columns
+    /// are headed `first` and `second`.
+    pub fn to_markdown(&self, first: &str, second: &str) -> String {
+        let mut out = String::new();
+        self.write_markdown(&mut out, first, second)
+            .expect("Writing to a String cannot fail");
+
+        out
+    }
+
+    fn write_markdown(&self, out: &mut String, first: &str, second: &str) -> fmt::Result {
+        for (i, (category, diff)) in self.categories.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "## {}", category)?;
+            writeln!(out)?;
+            writeln!(out, "| Identifier | {} | {} |", first, second)?;
+            writeln!(out, "| --- | --- | --- |")?;
+
+            let mut rows = BTreeMap::new();
+            for (identifier, value) in &diff.added {
+                rows.insert(identifier, (None, Some(value)));
+            }
+            for (identifier, value) in &diff.removed {
+                rows.insert(identifier, (Some(value), None));
+            }
+            for (identifier, change) in &diff.changed {
+                rows.insert(identifier, (Some(&change.first), Some(&change.second)));
+            }
+
+            let cell = |value: Option<&Value>| match value {
+                Some(value) => format!("`{}`", value).replace('|', "\\|"),
+                None => String::from("_undefined_"),
+            };
+            for (identifier, (first, second)) in rows {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    identifier,
+                    cell(first),
+                    cell(second)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diff {
+    /// Formats the differences as plain text, with one line per definition
+    /// prefixed by `+` if it was added, `-` if it was removed, or `~` if it
+    /// was changed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (category, diff) in &self.categories {
+            writeln!(f, "{}:", category)?;
+            for (identifier, value) in &diff.added {
+                writeln!(f, "  + {} = {}", identifier, value)?;
+            }
+            for (identifier, value) in &diff.removed {
+                writeln!(f, "  - {} = {}", identifier, value)?;
+            }
+            for (identifier, change) in &diff.changed {
+                writeln!(
+                    f,
+                    "  ~ {} = {} -> {}",
+                    identifier, change.first, change.second
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff() -> Diff {
+        let first = HashMap::from([
+            (String::from("XCHAL_HAVE_FP"), Value::Integer(1)),
+            (String::from("XCHAL_ICACHE_SIZE"), Value::Integer(16384)),
+            (
+                String::from("XCHAL_CORE_ID"),
+                Value::String(String::from("a|b")),
+            ),
+            (String::from("XCHAL_NUM_INTERRUPTS"), Value::Integer(32)),
+        ]);
+        let second = HashMap::from([
+            (String::from("XCHAL_HAVE_FP"), Value::Integer(0)),
+            (String::from("XCHAL_HAVE_MAC16"), Value::Integer(1)),
+            (String::from("XCHAL_ICACHE_SIZE"), Value::Integer(16384)),
+            (
+                String::from("XCHAL_CORE_ID"),
+                Value::String(String::from("c")),
+            ),
+        ]);
+
+        Diff::new(&first, &second)
+    }
+
+    #[test]
+    fn categories() {
+        let cases = [
+            ("XCHAL_INT0_LEVEL", Category::Interrupts),
+            ("XCHAL_NUM_INTERRUPTS", Category::Interrupts),
+            ("XCHAL_EXCM_LEVEL", Category::Interrupts),
+            ("XCHAL_TIMER0_INTERRUPT", Category::Interrupts),
+            ("XCHAL_ICACHE_SIZE", Category::Caches),
+            ("XCHAL_DCACHE_LINESIZE", Category::Caches),
+            ("XCHAL_INSTRAM0_VADDR", Category::Memory),
+            ("XCHAL_INSTROM0_SIZE", Category::Memory),
+            ("XCHAL_NUM_DATARAM", Category::Memory),
+            ("XCHAL_RESET_VECTOR0_VADDR", Category::Vectors),
+            ("XCHAL_KERNEL_VECOFS", Category::Vectors),
+            ("XCHAL_VECBASE_RESET_VADDR", Category::Vectors),
+            ("XCHAL_HAVE_FP", Category::Features),
+            ("XCHAL_CORE_ID", Category::Other),
+            ("CORE_ISA_H", Category::Other),
+            // Feature flags take precedence over the vector checks.
+            ("XCHAL_HAVE_VECBASE", Category::Features),
+            ("XCHAL_HAVE_INTERRUPTS", Category::Features),
+            // Vectors take precedence over the interrupt checks.
+            ("XCHAL_INTLEVEL2_VECTOR_VADDR", Category::Vectors),
+            ("XCHAL_NMI_VECTOR_VADDR", Category::Vectors),
+            // An `INSTR*` prefix must not be mistaken for `INT*`.
+            ("XCHAL_INSTRAM1_PADDR", Category::Memory),
+        ];
+
+        for (identifier, category) in cases {
+            assert_eq!(Category::of(identifier), category, "{}", identifier);
+        }
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            diff().to_string(),
+            "interrupts:\n  \
+             - XCHAL_NUM_INTERRUPTS = 32\n\
+             features:\n  \
+             + XCHAL_HAVE_MAC16 = 1\n  \
+             ~ XCHAL_HAVE_FP = 1 -> 0\n\
+             other:\n  \
+             ~ XCHAL_CORE_ID = \"a|b\" -> \"c\"\n"
+        );
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            diff().to_markdown("esp32", "esp32s3"),
+            "## interrupts\n\
+             \n\
+             | Identifier | esp32 | esp32s3 |\n\
+             | --- | --- | --- |\n\
+             | `XCHAL_NUM_INTERRUPTS` | `32` | _undefined_ |\n\
+             \n\
+             ## features\n\
+             \n\
+             | Identifier | esp32 | esp32s3 |\n\
+             | --- | --- | --- |\n\
+             | `XCHAL_HAVE_FP` | `1` | `0` |\n\
+             | `XCHAL_HAVE_MAC16` | _undefined_ | `1` |\n\
+             \n\
+             ## other\n\
+             \n\
+             | Identifier | esp32 | esp32s3 |\n\
+             | --- | --- | --- |\n\
+             | `XCHAL_CORE_ID` | `\"a\\|b\"` | `\"c\"` |\n"
+        );
+    }
+}
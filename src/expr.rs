@@ -0,0 +1,642 @@
+//! Evaluation of C integer constant expressions, as used both in conditional
+//! directives and in the replacement text of definitions.
+//!
+//! Operands are typed as in C, with the usual arithmetic conversions applied
+//! to the operands of binary operators. Arithmetic wraps on overflow.
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::literal::{self, IntegerType};
+
+/// Resolves the identifiers encountered while evaluating an expression.
+pub(crate) trait Context {
+    /// Whether a macro with the given identifier is currently defined.
+    fn is_defined(&self, identifier: &str) -> bool;
+
+    /// The integer value of the given identifier.
+    fn resolve(&self, identifier: &str) -> Result<i64>;
+
+    /// Whether all signed and unsigned operands are widened to 64 bits, as
+    /// they are in conditional directives.
+    fn widen(&self) -> bool {
+        false
+    }
+}
+
+/// Evaluate the expression `source`, resolving identifiers using `context`.
+///
+/// Results of an unsigned type are returned as their unsigned value, except
+/// for 64-bit values exceeding `i64::MAX`, which wrap around.
+pub(crate) fn evaluate(source: &str, context: &impl Context) -> Result<i64> {
+    let tokens = tokenize(source)?;
+    let mut parser = ExprParser { tokens, pos: 0 };
+
+    let expr = parser.parse_conditional()?;
+    if let Some(token) = parser.peek() {
+        bail!("Unexpected token '{}' in expression: {}", token, source);
+    }
+
+    Ok(expr.evaluate(context)?.value)
+}
+
+/// A typed integer value.
+///
+/// The value is stored sign- or zero-extended from the width of its type, so
+/// that it may be used directly unless the type is `unsigned long long`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Integer {
+    value: i64,
+    ty: IntegerType,
+}
+
+impl Integer {
+    /// Create an integer of type `ty` from the low bits of `value`.
+    fn new(value: i64, ty: IntegerType) -> Self {
+        let value = match (ty.bits(), ty.is_unsigned()) {
+            (32, false) => value as i32 as i64,
+            (32, true) => value as u32 as i64,
+            _ => value,
+        };
+
+        Self { value, ty }
+    }
+
+    fn bool(value: bool) -> Self {
+        Self::new(value as i64, IntegerType::Int)
+    }
+
+    /// Widen to 64 bits, preserving signedness.
+    fn widen(self) -> Self {
+        Self::new(self.value, widened(self.ty))
+    }
+
+    fn convert(self, ty: IntegerType) -> Self {
+        Self::new(self.value, ty)
+    }
+
+    fn is_true(&self) -> bool {
+        self.value != 0
+    }
+}
+
+/// The 64-bit type with the same signedness as `ty`.
+fn widened(ty: IntegerType) -> IntegerType {
+    if ty.is_unsigned() {
+        IntegerType::UnsignedLongLong
+    } else {
+        IntegerType::LongLong
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Integer(Integer),
+    Identifier(String),
+    Punct(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Integer(integer) => write!(f, "{}", integer.value),
+            Token::Identifier(identifier) => write!(f, "{}", identifier),
+            Token::Punct(punct) => write!(f, "{}", punct),
+        }
+    }
+}
+
+// Longer punctuators must precede any of their prefixes.
+const PUNCTUATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "(", ")", "!", "~", "<", ">", "+", "-", "*",
+    "/", "%", "&", "|", "^", "?", ":",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        let len = if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            let literal = literal::parse_integer(&rest[..len])?;
+            tokens.push(Token::Integer(Integer::new(
+                literal.value as i64,
+                literal.ty,
+            )));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..len].to_string()));
+            len
+        } else if c == '\'' {
+            // The constant ends at the first quote which is not escaped.
+            let mut escaped = false;
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| {
+                    let end = !escaped && c == '\'';
+                    escaped = !escaped && c == '\\';
+                    end
+                })
+                .map(|(i, _)| i + 1)
+                .ok_or_else(|| {
+                    anyhow!("Unterminated character constant in expression: {}", source)
+                })?;
+            let literal = literal::parse_character(&rest[..len])?;
+            tokens.push(Token::Integer(Integer::new(
+                literal.value as i64,
+                literal.ty,
+            )));
+            len
+        } else if let Some(punct) = PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+            tokens.push(Token::Punct(punct));
+            punct.len()
+        } else {
+            bail!("Unexpected character '{}' in expression: {}", c, source);
+        };
+
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    LogicalOr,
+    LogicalAnd,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    fn from_punct(punct: &str) -> Option<Self> {
+        let op = match punct {
+            "||" => BinaryOp::LogicalOr,
+            "&&" => BinaryOp::LogicalAnd,
+            "|" => BinaryOp::BitOr,
+            "^" => BinaryOp::BitXor,
+            "&" => BinaryOp::BitAnd,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            ">" => BinaryOp::Gt,
+            "<=" => BinaryOp::Le,
+            ">=" => BinaryOp::Ge,
+            "<<" => BinaryOp::Shl,
+            ">>" => BinaryOp::Shr,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::LogicalOr => 1,
+            BinaryOp::LogicalAnd => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Eq | BinaryOp::Ne => 6,
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => 7,
+            BinaryOp::Shl | BinaryOp::Shr => 8,
+            BinaryOp::Add | BinaryOp::Sub => 9,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 10,
+        }
+    }
+
+    fn apply(&self, lhs: Integer, rhs: Integer) -> Result<Integer> {
+        // The result of a shift has the type of its left operand, while the
+        // operands of other operators are converted to their common type.
+        let ty = match self {
+            BinaryOp::Shl | BinaryOp::Shr => lhs.ty,
+            _ => lhs.ty.common(rhs.ty),
+        };
+        let unsigned = ty.is_unsigned();
+        let (lhs, rhs) = (lhs.convert(ty).value, rhs.convert(ty).value);
+
+        // Comparisons of 64-bit unsigned values cannot be made on their signed
+        // representations.
+        let compare = |lhs: i64, rhs: i64| {
+            if unsigned {
+                (lhs as u64).cmp(&(rhs as u64))
+            } else {
+                lhs.cmp(&rhs)
+            }
+        };
+
+        let value = match self {
+            BinaryOp::LogicalOr | BinaryOp::LogicalAnd => return Ok(Integer::bool(rhs != 0)),
+            BinaryOp::Eq => return Ok(Integer::bool(lhs == rhs)),
+            BinaryOp::Ne => return Ok(Integer::bool(lhs != rhs)),
+            BinaryOp::Lt => return Ok(Integer::bool(compare(lhs, rhs).is_lt())),
+            BinaryOp::Gt => return Ok(Integer::bool(compare(lhs, rhs).is_gt())),
+            BinaryOp::Le => return Ok(Integer::bool(compare(lhs, rhs).is_le())),
+            BinaryOp::Ge => return Ok(Integer::bool(compare(lhs, rhs).is_ge())),
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::Shl | BinaryOp::Shr => {
+                let shift = u32::try_from(rhs)
+                    .ok()
+                    .filter(|shift| *shift < ty.bits())
+                    .ok_or_else(|| anyhow!("Shift amount out of range: {}", rhs))?;
+
+                match self {
+                    BinaryOp::Shl => lhs.wrapping_shl(shift),
+                    _ if unsigned => ((lhs as u64) >> shift) as i64,
+                    _ => lhs >> shift,
+                }
+            }
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+            BinaryOp::Mul => lhs.wrapping_mul(rhs),
+            BinaryOp::Div | BinaryOp::Rem if rhs == 0 => bail!("Division by zero"),
+            BinaryOp::Div if unsigned => ((lhs as u64) / (rhs as u64)) as i64,
+            BinaryOp::Rem if unsigned => ((lhs as u64) % (rhs as u64)) as i64,
+            BinaryOp::Div => lhs.wrapping_div(rhs),
+            BinaryOp::Rem => lhs.wrapping_rem(rhs),
+        };
+
+        Ok(Integer::new(value, ty))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnaryOp {
+    Plus,
+    Minus,
+    Not,
+    BitNot,
+}
+
+impl UnaryOp {
+    fn from_punct(punct: &str) -> Option<Self> {
+        let op = match punct {
+            "+" => UnaryOp::Plus,
+            "-" => UnaryOp::Minus,
+            "!" => UnaryOp::Not,
+            "~" => UnaryOp::BitNot,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    fn apply(&self, operand: Integer) -> Integer {
+        let value = operand.value;
+        match self {
+            UnaryOp::Plus => operand,
+            UnaryOp::Minus => Integer::new(value.wrapping_neg(), operand.ty),
+            UnaryOp::Not => Integer::bool(value == 0),
+            UnaryOp::BitNot => Integer::new(!value, operand.ty),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Integer(Integer),
+    Identifier(String),
+    Defined(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, context: &impl Context) -> Result<Integer> {
+        let value = match self {
+            Expr::Integer(integer) => *integer,
+            Expr::Identifier(identifier) => {
+                let value = context.resolve(identifier)?;
+                Integer::new(value, resolved_type(value))
+            }
+            Expr::Defined(identifier) => Integer::bool(context.is_defined(identifier)),
+            Expr::Unary(op, expr) => op.apply(expr.evaluate(context)?),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(context)?;
+
+                // The logical operators must not evaluate their right-hand side
+                // unless it is required to determine the result.
+                match op {
+                    BinaryOp::LogicalOr if lhs.is_true() => return Ok(Integer::bool(true)),
+                    BinaryOp::LogicalAnd if !lhs.is_true() => return Ok(Integer::bool(false)),
+                    _ => {}
+                }
+
+                op.apply(lhs, rhs.evaluate(context)?)?
+            }
+            Expr::Conditional(cond, then, otherwise) => {
+                // Only the selected operand is evaluated, but the result has
+                // the common type of both.
+                let ty = then.ty(context).common(otherwise.ty(context));
+                let value = if cond.evaluate(context)?.is_true() {
+                    then.evaluate(context)?
+                } else {
+                    otherwise.evaluate(context)?
+                };
+
+                value.convert(ty)
+            }
+        };
+
+        if context.widen() {
+            Ok(value.widen())
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// The type of the expression's value, determined without evaluating it so
+    /// that no errors are raised by operands which would not be evaluated.
+    fn ty(&self, context: &impl Context) -> IntegerType {
+        let ty = match self {
+            Expr::Integer(integer) => integer.ty,
+            // An identifier which cannot be resolved is assumed to be an
+            // `int`; evaluating it would fail regardless.
+            Expr::Identifier(identifier) => context
+                .resolve(identifier)
+                .map_or(IntegerType::Int, resolved_type),
+            Expr::Defined(_) => IntegerType::Int,
+            Expr::Unary(UnaryOp::Not, _) => IntegerType::Int,
+            Expr::Unary(_, expr) => expr.ty(context),
+            Expr::Binary(op, lhs, rhs) => match op {
+                BinaryOp::LogicalOr
+                | BinaryOp::LogicalAnd
+                | BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Gt
+                | BinaryOp::Le
+                | BinaryOp::Ge => IntegerType::Int,
+                BinaryOp::Shl | BinaryOp::Shr => lhs.ty(context),
+                _ => lhs.ty(context).common(rhs.ty(context)),
+            },
+            Expr::Conditional(_, then, otherwise) => then.ty(context).common(otherwise.ty(context)),
+        };
+
+        if context.widen() {
+            widened(ty)
+        } else {
+            ty
+        }
+    }
+}
+
+/// The type of a resolved value, which is unknown, so the narrowest signed
+/// type which can represent it is assumed.
+fn resolved_type(value: i64) -> IntegerType {
+    match i32::try_from(value) {
+        Ok(_) => IntegerType::Int,
+        Err(_) => IntegerType::LongLong,
+    }
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unexpected end of expression"))?;
+        self.pos += 1;
+
+        Ok(token)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        match self.next()? {
+            Token::Punct(p) if p == punct => Ok(()),
+            token => bail!("Expected '{}', found '{}'", punct, token),
+        }
+    }
+
+    /// Parse a (right-associative) conditional expression, `a ? b : c`.
+    fn parse_conditional(&mut self) -> Result<Expr> {
+        let cond = self.parse_binary(0)?;
+        if self.peek() != Some(&Token::Punct("?")) {
+            return Ok(cond);
+        }
+        self.pos += 1;
+
+        let then = self.parse_conditional()?;
+        self.expect(":")?;
+        let otherwise = self.parse_conditional()?;
+
+        Ok(Expr::Conditional(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    /// Parse a binary expression using precedence climbing, consuming only
+    /// operators which bind at least as tightly as `min_precedence`.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(Token::Punct(punct)) = self.peek() {
+            let op = match BinaryOp::from_punct(punct) {
+                Some(op) if op.precedence() >= min_precedence => op,
+                _ => break,
+            };
+            self.pos += 1;
+
+            let rhs = self.parse_binary(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        let expr = match self.next()? {
+            Token::Integer(integer) => Expr::Integer(integer),
+            Token::Identifier(identifier) if identifier == "defined" => {
+                // Both 'defined X' and 'defined(X)' are valid.
+                let parenthesized = self.peek() == Some(&Token::Punct("("));
+                if parenthesized {
+                    self.pos += 1;
+                }
+
+                let identifier = match self.next()? {
+                    Token::Identifier(identifier) => identifier,
+                    token => bail!("Expected identifier after 'defined', found '{}'", token),
+                };
+
+                if parenthesized {
+                    self.expect(")")?;
+                }
+
+                Expr::Defined(identifier)
+            }
+            Token::Identifier(identifier) => Expr::Identifier(identifier),
+            Token::Punct("(") => {
+                let expr = self.parse_conditional()?;
+                self.expect(")")?;
+
+                expr
+            }
+            Token::Punct(punct) => match UnaryOp::from_punct(punct) {
+                Some(op) => Expr::Unary(op, Box::new(self.parse_unary()?)),
+                None => bail!("Unexpected token '{}' in expression", punct),
+            },
+        };
+
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A context in which `ONE` is defined as 1 and `ZERO` as 0.
+    struct Defines {
+        widen: bool,
+    }
+
+    impl Context for Defines {
+        fn is_defined(&self, identifier: &str) -> bool {
+            matches!(identifier, "ONE" | "ZERO")
+        }
+
+        fn resolve(&self, identifier: &str) -> Result<i64> {
+            match identifier {
+                "ONE" => Ok(1),
+                "ZERO" => Ok(0),
+                _ => bail!("Unknown identifier: {}", identifier),
+            }
+        }
+
+        fn widen(&self) -> bool {
+            self.widen
+        }
+    }
+
+    fn eval(source: &str) -> Result<i64> {
+        evaluate(source, &Defines { widen: false })
+    }
+
+    #[test]
+    fn unsigned_comparison() {
+        assert_eq!(eval("-1 < 0u").unwrap(), 0);
+        assert_eq!(eval("-1 < 0").unwrap(), 1);
+        assert_eq!(evaluate("-1 < 0u", &Defines { widen: true }).unwrap(), 0);
+
+        // 0x80000000 is unsigned int, while 2147483648 is long long.
+        assert_eq!(eval("0x80000000 > -1").unwrap(), 0);
+        assert_eq!(eval("2147483648 > -1").unwrap(), 1);
+    }
+
+    #[test]
+    fn shift_out_of_range() {
+        let err = eval("1 << 40").unwrap_err();
+        assert!(err.to_string().contains("Shift amount out of range"));
+        assert_eq!(eval("1ULL << 40").unwrap(), 1 << 40);
+        assert_eq!(eval("1u << 31").unwrap(), 0x8000_0000);
+    }
+
+    #[test]
+    fn identifiers_are_not_literals() {
+        assert_eq!(
+            tokenize("ABC").unwrap(),
+            [Token::Identifier("ABC".to_string())]
+        );
+        assert!(eval("ABC").is_err());
+    }
+
+    #[test]
+    fn conditional_is_right_associative() {
+        assert_eq!(eval("1 ? 2 : 0 ? 3 : 4").unwrap(), 2);
+        assert_eq!(eval("0 ? 2 : 0 ? 3 : 4").unwrap(), 4);
+        assert_eq!(eval("0 ? 2 : 1 ? 3 : 4").unwrap(), 3);
+        assert_eq!(eval("ONE ? ZERO ? 5 : 6 : 7").unwrap(), 6);
+    }
+
+    #[test]
+    fn conditional_common_type() {
+        // The result is converted to `unsigned int`, even though the unsigned
+        // operand is not the one selected.
+        assert_eq!(eval("1 ? -1 : 0u").unwrap(), 0xffff_ffff);
+        assert_eq!(eval("(0 ? 0u : -1) > 0").unwrap(), 1);
+        assert_eq!(eval("(1 ? -1 : 0) > 0").unwrap(), 0);
+        assert_eq!(eval("1 ? -1 : 0ULL").unwrap(), -1);
+        assert_eq!(eval("(ONE ? -1 : 0LL) < 0u").unwrap(), 1);
+        assert_eq!(
+            evaluate("1 ? -1 : 0u", &Defines { widen: true }).unwrap(),
+            -1
+        );
+
+        // The type of the unselected operand is found without evaluating it.
+        assert_eq!(eval("0 ? 1u / 0 : -1").unwrap(), 0xffff_ffff);
+        assert_eq!(eval("1 ? -1 : UNDEFINED").unwrap(), -1);
+    }
+
+    #[test]
+    fn character_constants() {
+        assert_eq!(eval("'a'").unwrap(), 0x61);
+        assert_eq!(eval("'\\'' + 1").unwrap(), 0x28);
+        assert_eq!(eval("'\\xff' > 0").unwrap(), 1);
+        assert_eq!(eval("'0' <= '5' && '5' <= '9'").unwrap(), 1);
+
+        let err = eval("'ab'").unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported character constant: 'ab'");
+        let err = eval("'a").unwrap_err();
+        assert!(err.to_string().contains("Unterminated character constant"));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(eval("0 && 1 / 0").unwrap(), 0);
+        assert_eq!(eval("1 || 1 / 0").unwrap(), 1);
+        assert_eq!(eval("ZERO && UNDEFINED").unwrap(), 0);
+        assert_eq!(eval("1 ? 2 : 1 / 0").unwrap(), 2);
+
+        let err = eval("1 && 1 / 0").unwrap_err();
+        assert_eq!(err.to_string(), "Division by zero");
+    }
+
+    #[test]
+    fn defined_operator() {
+        assert_eq!(eval("defined ONE && defined(ZERO)").unwrap(), 1);
+        assert_eq!(eval("!defined(OTHER)").unwrap(), 1);
+        assert!(eval("defined(1)").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! A typed model of the processor options described by the `XCHAL_HAVE_*`
+//! definitions, along with a few options which are implied by others.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{flag, Value};
+
+macro_rules! features {
+    ($($(#[$doc:meta])* $field:ident => $identifier:literal,)*) => {
+        /// The processor options of a chip, each of which is either present or
+        /// absent.
+        ///
+        /// ```no_run
+        /// use xtensa_core_isa::{parse_defines, Chip, CoreFeatures};
+        ///
+        /// let esp32 = CoreFeatures::from_defines(&parse_defines(Chip::Esp32)?)?;
+        /// let esp32s2 = CoreFeatures::from_defines(&parse_defines(Chip::Esp32s2)?)?;
+        /// println!("{:?}", esp32.compare(&esp32s2));
+        /// # Ok::<(), anyhow::Error>(())
+        /// ```
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+        pub struct CoreFeatures {
+            $(
+                $(#[$doc])*
+                pub $field: bool,
+            )*
+        }
+
+        impl CoreFeatures {
+            /// Build the features from the `XCHAL_HAVE_*` definitions, treating
+            /// any which are missing as absent.
+            pub fn from_defines(defines: &HashMap<String, Value>) -> Result<Self> {
+                Ok(Self {
+                    $($field: flag(defines, $identifier)?,)*
+                })
+            }
+
+            /// The identifier of every feature's definition, along with
+            /// whether it is present.
+            pub fn iter(&self) -> impl Iterator<Item = (&'static str, bool)> {
+                [$(($identifier, self.$field),)*].into_iter()
+            }
+        }
+    };
+}
+
+features! {
+    /// Big-endian byte order.
+    be => "XCHAL_HAVE_BE",
+    /// Relocatable vectors.
+    vecbase => "XCHAL_HAVE_VECBASE",
+    /// Windowed registers.
+    windowed => "XCHAL_HAVE_WINDOWED",
+    call4and12 => "XCHAL_HAVE_CALL4AND12",
+    /// Code density (narrow instructions).
+    density => "XCHAL_HAVE_DENSITY",
+    /// Zero-overhead loops.
+    loops => "XCHAL_HAVE_LOOPS",
+    loop_buffer => "XCHAL_HAVE_LOOP_BUFFER",
+    nsa => "XCHAL_HAVE_NSA",
+    minmax => "XCHAL_HAVE_MINMAX",
+    sext => "XCHAL_HAVE_SEXT",
+    depbits => "XCHAL_HAVE_DEPBITS",
+    clamps => "XCHAL_HAVE_CLAMPS",
+    mul16 => "XCHAL_HAVE_MUL16",
+    mul32 => "XCHAL_HAVE_MUL32",
+    mul32_high => "XCHAL_HAVE_MUL32_HIGH",
+    div32 => "XCHAL_HAVE_DIV32",
+    l32r => "XCHAL_HAVE_L32R",
+    absolute_literals => "XCHAL_HAVE_ABSOLUTE_LITERALS",
+    const16 => "XCHAL_HAVE_CONST16",
+    addx => "XCHAL_HAVE_ADDX",
+    exclusive => "XCHAL_HAVE_EXCLUSIVE",
+    wide_branches => "XCHAL_HAVE_WIDE_BRANCHES",
+    predicted_branches => "XCHAL_HAVE_PREDICTED_BRANCHES",
+    abs => "XCHAL_HAVE_ABS",
+    release_sync => "XCHAL_HAVE_RELEASE_SYNC",
+    /// The conditional store instruction, used for atomics.
+    s32c1i => "XCHAL_HAVE_S32C1I",
+    speculation => "XCHAL_HAVE_SPECULATION",
+    full_reset => "XCHAL_HAVE_FULL_RESET",
+    prefetch => "XCHAL_HAVE_PREFETCH",
+    /// The `THREADPTR` user register.
+    threadptr => "XCHAL_HAVE_THREADPTR",
+    booleans => "XCHAL_HAVE_BOOLEANS",
+    mac16 => "XCHAL_HAVE_MAC16",
+    /// Single-precision floating point.
+    fp => "XCHAL_HAVE_FP",
+    fp_div => "XCHAL_HAVE_FP_DIV",
+    fp_recip => "XCHAL_HAVE_FP_RECIP",
+    fp_sqrt => "XCHAL_HAVE_FP_SQRT",
+    fp_rsqrt => "XCHAL_HAVE_FP_RSQRT",
+    /// Double-precision floating point.
+    dfp => "XCHAL_HAVE_DFP",
+    dfp_div => "XCHAL_HAVE_DFP_DIV",
+    dfp_recip => "XCHAL_HAVE_DFP_RECIP",
+    dfp_sqrt => "XCHAL_HAVE_DFP_SQRT",
+    dfp_rsqrt => "XCHAL_HAVE_DFP_RSQRT",
+    /// Acceleration instructions for double-precision floating point in
+    /// software.
+    dfp_accel => "XCHAL_HAVE_DFP_ACCEL",
+    /// Coprocessors, enabled using the `CPENABLE` register.
+    cp => "XCHAL_HAVE_CP",
+    prid => "XCHAL_HAVE_PRID",
+    exceptions => "XCHAL_HAVE_EXCEPTIONS",
+    interrupts => "XCHAL_HAVE_INTERRUPTS",
+    highpri_interrupts => "XCHAL_HAVE_HIGHPRI_INTERRUPTS",
+    nmi => "XCHAL_HAVE_NMI",
+    ccount => "XCHAL_HAVE_CCOUNT",
+    vector_select => "XCHAL_HAVE_VECTOR_SELECT",
+    debug => "XCHAL_HAVE_DEBUG",
+    ocd => "XCHAL_HAVE_OCD",
+    trax => "XCHAL_HAVE_TRAX",
+    pif => "XCHAL_HAVE_PIF",
+    extern_regs => "XCHAL_HAVE_EXTERN_REGS",
+    mem_ecc_parity => "XCHAL_HAVE_MEM_ECC_PARITY",
+    cacheattr => "XCHAL_HAVE_CACHEATTR",
+    mimic_cacheattr => "XCHAL_HAVE_MIMIC_CACHEATTR",
+    xlt_cacheattr => "XCHAL_HAVE_XLT_CACHEATTR",
+    spanning_way => "XCHAL_HAVE_SPANNING_WAY",
+    ptp_mmu => "XCHAL_HAVE_PTP_MMU",
+    mpu => "XCHAL_HAVE_MPU",
+    icache_dyn_ways => "XCHAL_HAVE_ICACHE_DYN_WAYS",
+    dcache_dyn_ways => "XCHAL_HAVE_DCACHE_DYN_WAYS",
+    /// The `MEMCTL` register, present whenever the loop buffer, cache
+    /// coherence or dynamic cache ways need controlling.
+    memctl => "XCHAL_USE_MEMCTL",
+    /// The `CCOMPAREn` timer registers, present if there is at least one
+    /// timer.
+    timers => "XCHAL_NUM_TIMERS",
+    /// The `MISCn` scratch registers, present if there is at least one.
+    misc_regs => "XCHAL_NUM_MISC_REGS",
+}
+
+/// The differences between two sets of features, by the identifiers of their
+/// definitions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureComparison {
+    /// The features which are only present in the first set.
+    pub only_first: Vec<&'static str>,
+    /// The features which are only present in the second set.
+    pub only_second: Vec<&'static str>,
+}
+
+impl FeatureComparison {
+    /// Whether the sets of features are the same.
+    pub fn is_empty(&self) -> bool {
+        self.only_first.is_empty() && self.only_second.is_empty()
+    }
+}
+
+impl CoreFeatures {
+    /// Compare these features with those of another chip.
+    pub fn compare(&self, other: &CoreFeatures) -> FeatureComparison {
+        let mut comparison = FeatureComparison::default();
+        for ((identifier, first), (_, second)) in self.iter().zip(other.iter()) {
+            match (first, second) {
+                (true, false) => comparison.only_first.push(identifier),
+                (false, true) => comparison.only_second.push(identifier),
+                _ => {}
+            }
+        }
+
+        comparison
+    }
+
+    /// Whether every feature present here is also present in `other`, such
+    /// that code restricted to these features may also run on the other chip.
+    pub fn is_subset_of(&self, other: &CoreFeatures) -> bool {
+        self.compare(other).only_first.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    fn features(source: &str) -> CoreFeatures {
+        CoreFeatures::from_defines(&parse_str_defines(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn comparison() {
+        let first = features("#define XCHAL_HAVE_FP 1\n#define XCHAL_HAVE_LOOPS 1");
+        let second = features(
+            "#define XCHAL_HAVE_FP 0\n#define XCHAL_HAVE_LOOPS 1\n#define XCHAL_HAVE_DFP 1",
+        );
+
+        assert!(first.fp && first.loops && !first.dfp);
+        assert_eq!(
+            first.compare(&second),
+            FeatureComparison {
+                only_first: vec!["XCHAL_HAVE_FP"],
+                only_second: vec!["XCHAL_HAVE_DFP"],
+            }
+        );
+        assert!(first.compare(&first).is_empty());
+
+        let loops = features("#define XCHAL_HAVE_LOOPS 1");
+        assert!(loops.is_subset_of(&first) && loops.is_subset_of(&second));
+        assert!(!first.is_subset_of(&second));
+    }
+
+    #[test]
+    fn non_integer_flags() {
+        assert!(features("#define XCHAL_HAVE_FP 2").fp);
+
+        let defines = parse_str_defines("#define XCHAL_HAVE_FP \"yes\"").unwrap();
+        assert!(CoreFeatures::from_defines(&defines).is_err());
+    }
+}
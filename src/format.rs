@@ -0,0 +1,151 @@
+//! Serialization of parsed definitions and typed models to text formats.
+
+use anyhow::Result;
+use serde::Serialize;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A text format to which definitions and models can be serialized.
+///
+/// Maps are emitted with their keys in sorted order, and structs with their
+/// fields in declaration order, so the output for a given configuration is
+/// always the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Serialize `value` in this format.
+    ///
+    /// ```
+    /// use xtensa_core_isa::{Format, Parser};
+    ///
+    /// let config = Parser::new().parse_str("#define XCHAL_HAVE_FP 1")?;
+    /// assert_eq!(Format::Toml.serialize(&config)?, "XCHAL_HAVE_FP = 1\n");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String> {
+        let output = match self {
+            Format::Json => serde_json::to_string_pretty(value)? + "\n",
+            Format::Toml => toml::to_string_pretty(value)?,
+            Format::Yaml => serde_yaml::to_string(value)?,
+        };
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::{CoreConfig, InterruptType, Parser, Value};
+
+    const HEADER: &str = r#"
+#define XCHAL_NUM_INTERRUPTS 32
+#define XCHAL_CORE_ID "ESP32_v3_49_prod"
+#define XCHAL_INT0_TYPE XTHAL_INTTYPE_TIMER
+#define XCHAL_ICACHE_SIZE (8 * 1024)
+#define XCHAL_HW_VERSION_NAME "1"
+"#;
+
+    fn config() -> CoreConfig {
+        Parser::new().parse_str(HEADER).unwrap()
+    }
+
+    fn deserialize(format: Format, output: &str) -> BTreeMap<String, Value> {
+        match format {
+            Format::Json => serde_json::from_str(output).unwrap(),
+            Format::Toml => toml::from_str(output).unwrap(),
+            Format::Yaml => serde_yaml::from_str(output).unwrap(),
+        }
+    }
+
+    #[test]
+    fn value_shape() {
+        let values = [
+            (Value::Integer(-1), json!(-1)),
+            (Value::String(String::from("1")), json!("1")),
+            (
+                Value::Interrupt(InterruptType::Timer),
+                json!("XTHAL_INTTYPE_TIMER"),
+            ),
+        ];
+
+        for (value, expected) in values {
+            assert_eq!(serde_json::to_value(&value).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn sorted_map() {
+        assert_eq!(
+            serde_json::to_value(config()).unwrap(),
+            json!({
+                "XCHAL_CORE_ID": "ESP32_v3_49_prod",
+                "XCHAL_HW_VERSION_NAME": "1",
+                "XCHAL_ICACHE_SIZE": 8192,
+                "XCHAL_INT0_TYPE": "XTHAL_INTTYPE_TIMER",
+                "XCHAL_NUM_INTERRUPTS": 32,
+            })
+        );
+
+        let output = Format::Json.serialize(&config()).unwrap();
+        let keys = output
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix('"')?.split('"').next())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "XCHAL_CORE_ID",
+                "XCHAL_HW_VERSION_NAME",
+                "XCHAL_ICACHE_SIZE",
+                "XCHAL_INT0_TYPE",
+                "XCHAL_NUM_INTERRUPTS",
+            ]
+        );
+    }
+
+    #[test]
+    fn stable_output() {
+        let expected = [
+            (
+                Format::Json,
+                "{\n  \"XCHAL_CORE_ID\": \"ESP32_v3_49_prod\",\n  \"XCHAL_HW_VERSION_NAME\": \"1\",\n  \"XCHAL_ICACHE_SIZE\": 8192,\n  \"XCHAL_INT0_TYPE\": \"XTHAL_INTTYPE_TIMER\",\n  \"XCHAL_NUM_INTERRUPTS\": 32\n}\n",
+            ),
+            (
+                Format::Toml,
+                "XCHAL_CORE_ID = \"ESP32_v3_49_prod\"\nXCHAL_HW_VERSION_NAME = \"1\"\nXCHAL_ICACHE_SIZE = 8192\nXCHAL_INT0_TYPE = \"XTHAL_INTTYPE_TIMER\"\nXCHAL_NUM_INTERRUPTS = 32\n",
+            ),
+            (
+                Format::Yaml,
+                "XCHAL_CORE_ID: ESP32_v3_49_prod\nXCHAL_HW_VERSION_NAME: '1'\nXCHAL_ICACHE_SIZE: 8192\nXCHAL_INT0_TYPE: XTHAL_INTTYPE_TIMER\nXCHAL_NUM_INTERRUPTS: 32\n",
+            ),
+        ];
+
+        for (format, expected) in expected {
+            // Parsing the header afresh must not change the output.
+            assert_eq!(format.serialize(&config()).unwrap(), expected, "{}", format);
+            assert_eq!(format.serialize(&config()).unwrap(), expected, "{}", format);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = config();
+        let map = config.to_map().into_iter().collect::<BTreeMap<_, _>>();
+
+        for format in Format::iter() {
+            let output = format.serialize(&config).unwrap();
+            assert_eq!(deserialize(format, &output), map, "{}", format);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt::{self, Write};
+
+use strum::IntoEnumIterator;
+
+use super::screaming_snake_case;
+use crate::{Chip, InterruptTable, InterruptType};
+
+/// Generate a Rust module describing the interrupts of `chip`.
+///
+/// The module is named after the chip (see [Chip::name]) and is
+/// self-contained, so the modules of several chips may be included alongside
+/// one another. It contains:
+///
+/// - an `InterruptType` enum equivalent to [InterruptType],
+/// - an `Interrupt` struct, and an `INTERRUPTS` array with one per interrupt,
+/// - `INTn_LEVEL` and `INTn_TYPE` constants for each interrupt,
+/// - a `LEVELn_MASK` constant for each priority level, and
+/// - a mask constant for each interrupt type present, such as `TIMER_MASK`.
+///
+/// ```no_run
+/// use xtensa_core_isa::{generate, parse_defines, Chip, InterruptTable};
+///
+/// let table = InterruptTable::from_defines(&parse_defines(Chip::Esp32)?)?;
+/// let out_dir = std::env::var("OUT_DIR")?;
+/// std::fs::write(
+///     format!("{}/interrupts.rs", out_dir),
+///     generate::rust_interrupts(Chip::Esp32, &table),
+/// )?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn rust_interrupts(chip: Chip, table: &InterruptTable) -> String {
+    let mut out = String::new();
+    write_interrupts(&mut out, chip, table).expect("Writing to a String cannot fail");
+
+    out
+}
+
+fn write_interrupts(out: &mut String, chip: Chip, table: &InterruptTable) -> fmt::Result {
+    writeln!(
+        out,
+        "// Generated by xtensa-core-isa from the `{}` overlay. Do not edit.",
+        chip
+    )?;
+    writeln!(out)?;
+    writeln!(out, "/// The interrupts of the {:?}.", chip)?;
+    writeln!(out, "#[allow(dead_code)]")?;
+    writeln!(out, "pub mod {} {{", chip.name())?;
+
+    writeln!(
+        out,
+        "    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]"
+    )?;
+    writeln!(out, "    pub enum InterruptType {{")?;
+    for kind in InterruptType::iter() {
+        writeln!(out, "        {:?},", kind)?;
+    }
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]"
+    )?;
+    writeln!(out, "    pub struct Interrupt {{")?;
+    writeln!(out, "        pub number: u32,")?;
+    writeln!(out, "        pub level: u32,")?;
+    writeln!(out, "        pub kind: InterruptType,")?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+
+    let count = table.interrupts.len();
+    writeln!(out, "    pub const NUM_INTERRUPTS: usize = {};", count)?;
+    writeln!(out)?;
+
+    writeln!(out, "    pub const INTERRUPTS: [Interrupt; {}] = [", count)?;
+    for interrupt in &table.interrupts {
+        writeln!(out, "        Interrupt {{")?;
+        writeln!(out, "            number: {},", interrupt.number)?;
+        writeln!(out, "            level: {},", interrupt.level)?;
+        writeln!(
+            out,
+            "            kind: InterruptType::{:?},",
+            interrupt.kind
+        )?;
+        writeln!(out, "        }},")?;
+    }
+    writeln!(out, "    ];")?;
+    writeln!(out)?;
+
+    for interrupt in &table.interrupts {
+        writeln!(
+            out,
+            "    pub const INT{}_LEVEL: u32 = {};",
+            interrupt.number, interrupt.level
+        )?;
+        writeln!(
+            out,
+            "    pub const INT{}_TYPE: InterruptType = InterruptType::{:?};",
+            interrupt.number, interrupt.kind
+        )?;
+    }
+    writeln!(out)?;
+
+    for (level, mask) in table.level_masks() {
+        writeln!(
+            out,
+            "    pub const LEVEL{}_MASK: u32 = {:#010x};",
+            level, mask
+        )?;
+    }
+    writeln!(out)?;
+
+    for (kind, mask) in table.type_masks() {
+        writeln!(
+            out,
+            "    pub const {}_MASK: u32 = {:#010x};",
+            screaming_snake_case(&format!("{:?}", kind)),
+            mask
+        )?;
+    }
+    writeln!(out, "}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    #[test]
+    fn constants() {
+        let defines = parse_str_defines(
+            "
+            #define XCHAL_NUM_INTERRUPTS 2
+            #define XCHAL_INT0_LEVEL 1
+            #define XCHAL_INT0_TYPE XTHAL_INTTYPE_TIMER
+            #define XCHAL_INT1_LEVEL 3
+            #define XCHAL_INT1_TYPE XTHAL_INTTYPE_EXTERN_EDGE
+        ",
+        )
+        .unwrap();
+        let table = InterruptTable::from_defines(&defines).unwrap();
+        let out = rust_interrupts(Chip::Esp32s2, &table);
+
+        for line in [
+            "pub mod esp32s2 {",
+            "    pub const NUM_INTERRUPTS: usize = 2;",
+            "    pub const INT1_LEVEL: u32 = 3;",
+            "    pub const INT1_TYPE: InterruptType = InterruptType::ExternEdge;",
+            "    pub const LEVEL1_MASK: u32 = 0x00000001;",
+            "    pub const LEVEL3_MASK: u32 = 0x00000002;",
+            "    pub const EXTERN_EDGE_MASK: u32 = 0x00000002;",
+            "    pub const TIMER_MASK: u32 = 0x00000001;",
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing: {}", line);
+        }
+        assert!(!out.contains("SOFTWARE_MASK"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+
+use crate::{Chip, MemoryKind, MemoryMap, MemoryRegion};
+
+/// A generator of GNU ld `MEMORY` blocks, with one region per local memory.
+///
+/// Regions are named after the memory in lowercase (such as `dataram1`)
+/// unless given another name, and part of a memory may be reserved at its
+/// start or end, in which case the region only covers the remainder. Memories
+/// are referred to by the prefix of their definitions, such as `DATARAM1`.
+///
+/// ```no_run
+/// use xtensa_core_isa::{generate::LinkerMemory, parse_defines, Chip, MemoryMap};
+///
+/// let memory = MemoryMap::from_defines(&parse_defines(Chip::Esp32)?)?;
+/// let script = LinkerMemory::new()
+///     .name("INSTRAM0", "iram_seg")
+///     .name("DATARAM0", "dram_seg")
+///     .reserve_start("DATARAM0", 0x2000)
+///     .generate(Chip::Esp32, &memory)?;
+/// println!("{}", script);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LinkerMemory {
+    names: Vec<(String, String)>,
+    reservations: Vec<(String, Reservation)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reservation {
+    Start(u32),
+    End(u32),
+}
+
+impl LinkerMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name the region of the memory `memory` (such as `INSTRAM0`) `name`.
+    pub fn name(mut self, memory: impl Into<String>, name: impl Into<String>) -> Self {
+        self.names.push((memory.into(), name.into()));
+        self
+    }
+
+    /// Reserve `size` bytes at the start of the memory `memory`.
+    pub fn reserve_start(mut self, memory: impl Into<String>, size: u32) -> Self {
+        self.reservations
+            .push((memory.into(), Reservation::Start(size)));
+        self
+    }
+
+    /// Reserve `size` bytes at the end of the memory `memory`.
+    pub fn reserve_end(mut self, memory: impl Into<String>, size: u32) -> Self {
+        self.reservations
+            .push((memory.into(), Reservation::End(size)));
+        self
+    }
+
+    /// Generate the `MEMORY` block for the local memories of `chip`.
+    ///
+    /// Fails if a name or reservation refers to a memory which the chip does
+    /// not have, or if the reservations of a memory exceed its size.
+    pub fn generate(&self, chip: Chip, memory: &MemoryMap) -> Result<String> {
+        let memories = self
+            .names
+            .iter()
+            .map(|(memory, _)| memory)
+            .chain(self.reservations.iter().map(|(memory, _)| memory));
+        for name in memories {
+            if !memory
+                .regions
+                .iter()
+                .any(|region| region.to_string() == *name)
+            {
+                bail!("The {:?} has no memory named {}", chip, name);
+            }
+        }
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "/* Generated by xtensa-core-isa from the `{}` overlay. Do not edit. */",
+            chip
+        )?;
+        writeln!(out)?;
+        writeln!(out, "MEMORY")?;
+        writeln!(out, "{{")?;
+
+        for region in &memory.regions {
+            let (start, end) = self.reserved(region);
+            if start as u64 + end as u64 > region.size as u64 {
+                bail!(
+                    "Reservations of {:#x} bytes exceed the size of {}",
+                    start as u64 + end as u64,
+                    region
+                );
+            }
+
+            if start != 0 || end != 0 {
+                writeln!(
+                    out,
+                    "  /* {:#x} bytes reserved at the start and {:#x} bytes at the end */",
+                    start, end
+                )?;
+            }
+            writeln!(
+                out,
+                "  {} ({}) : ORIGIN = {:#010x}, LENGTH = {:#010x}",
+                self.region_name(region),
+                attributes(region.kind),
+                region.vaddr as u64 + start as u64,
+                region.size - start - end
+            )?;
+        }
+
+        writeln!(out, "}}")?;
+
+        Ok(out)
+    }
+
+    fn region_name(&self, region: &MemoryRegion) -> String {
+        let memory = region.to_string();
+        match self.names.iter().rev().find(|(named, _)| *named == memory) {
+            Some((_, name)) => name.clone(),
+            None => memory.to_lowercase(),
+        }
+    }
+
+    /// The number of bytes reserved at the start and end of the memory.
+    fn reserved(&self, region: &MemoryRegion) -> (u32, u32) {
+        let memory = region.to_string();
+        self.reservations
+            .iter()
+            .filter(|(reserved, _)| *reserved == memory)
+            .fold((0, 0), |(start, end), (_, reservation)| match reservation {
+                Reservation::Start(size) => (start.saturating_add(*size), end),
+                Reservation::End(size) => (start, end.saturating_add(*size)),
+            })
+    }
+}
+
+/// The access attributes of a region of the given kind of memory.
+fn attributes(kind: MemoryKind) -> &'static str {
+    match kind {
+        MemoryKind::InstRom => "rx",
+        MemoryKind::InstRam | MemoryKind::URam => "rwx",
+        MemoryKind::DataRom => "r",
+        MemoryKind::DataRam | MemoryKind::Xlmi => "rw",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EccParity;
+
+    fn memory() -> MemoryMap {
+        let region = |kind, vaddr, size| MemoryRegion {
+            kind,
+            index: 0,
+            vaddr,
+            paddr: vaddr,
+            size,
+            ecc_parity: EccParity::None,
+        };
+
+        MemoryMap {
+            regions: vec![
+                region(MemoryKind::InstRam, 0x4008_0000, 0x20000),
+                region(MemoryKind::DataRam, 0x3ffe_0000, 0x20000),
+            ],
+        }
+    }
+
+    #[test]
+    fn regions() {
+        let script = LinkerMemory::new()
+            .name("INSTRAM0", "iram_seg")
+            .reserve_start("DATARAM0", 0x1000)
+            .reserve_start("DATARAM0", 0x1000)
+            .reserve_end("DATARAM0", 0x100)
+            .generate(Chip::Esp32, &memory())
+            .unwrap();
+
+        let lines = script.lines().skip(2).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "MEMORY",
+                "{",
+                "  iram_seg (rwx) : ORIGIN = 0x40080000, LENGTH = 0x00020000",
+                "  /* 0x2000 bytes reserved at the start and 0x100 bytes at the end */",
+                "  dataram0 (rw) : ORIGIN = 0x3ffe2000, LENGTH = 0x0001df00",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn reservation_exceeding_region() {
+        let err = LinkerMemory::new()
+            .reserve_start("INSTRAM0", 0x18000)
+            .reserve_end("INSTRAM0", 0x8001)
+            .generate(Chip::Esp32, &memory())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Reservations of 0x20001 bytes exceed the size of INSTRAM0"
+        );
+
+        // Saturating sums must not wrap around to a small reservation.
+        let err = LinkerMemory::new()
+            .reserve_start("INSTRAM0", u32::MAX)
+            .reserve_start("INSTRAM0", 2)
+            .generate(Chip::Esp32, &memory())
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Reservations of"));
+    }
+
+    #[test]
+    fn unknown_memory() {
+        let err = LinkerMemory::new()
+            .name("DATARAM1", "dram1_seg")
+            .generate(Chip::Esp32, &memory())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "The Esp32 has no memory named DATARAM1");
+    }
+}
@@ -0,0 +1,30 @@
+//! Generators for source files derived from a chip's configuration.
+//!
+//! Each generator returns the contents of the file as a [String], which is
+//! left to the caller to write wherever it is needed (for example, to
+//! `OUT_DIR` from a build script).
+
+pub use self::{
+    interrupts::rust_interrupts,
+    linker::LinkerMemory,
+    target::{llvm_cpu, llvm_features, target_features, target_json},
+    vectors::assembly_vectors,
+};
+
+mod interrupts;
+mod linker;
+mod target;
+mod vectors;
+
+/// Convert an identifier in `CamelCase` to `SCREAMING_SNAKE_CASE`.
+fn screaming_snake_case(identifier: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in identifier.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+    }
+
+    result
+}
@@ -0,0 +1,178 @@
+use serde_json::json;
+
+use crate::{Chip, CoreFeatures};
+
+/// The names of the LLVM Xtensa target features corresponding to the
+/// features present in `features`, in a fixed order.
+///
+/// The `ATOMCTL` register has no definition of its own, being part of the
+/// conditional store option, so `atomctl` accompanies `s32c1i`. Likewise
+/// `regprotect` is enabled by either form of region protection, with or
+/// without translation.
+pub fn llvm_features(features: &CoreFeatures) -> Vec<&'static str> {
+    [
+        (features.density, "density"),
+        (features.windowed, "windowed"),
+        (features.booleans, "bool"),
+        (features.loops, "loop"),
+        (features.sext, "sext"),
+        (features.nsa, "nsa"),
+        (features.clamps, "clamps"),
+        (features.minmax, "minmax"),
+        (features.mac16, "mac16"),
+        (features.mul16, "mul16"),
+        (features.mul32, "mul32"),
+        (features.mul32_high, "mul32high"),
+        (features.div32, "div32"),
+        (features.fp, "fp"),
+        (features.dfp_accel, "dfpaccel"),
+        (features.s32c1i, "s32c1i"),
+        (features.s32c1i, "atomctl"),
+        (features.memctl, "memctl"),
+        (features.threadptr, "threadptr"),
+        (features.debug, "debug"),
+        (features.exceptions, "exception"),
+        (features.interrupts, "interrupt"),
+        (features.highpri_interrupts, "highpriinterrupts"),
+        (features.cp, "coprocessor"),
+        (features.vecbase, "rvector"),
+        (features.timers, "timerint"),
+        (features.prid, "prid"),
+        (
+            features.mimic_cacheattr || features.xlt_cacheattr,
+            "regprotect",
+        ),
+        (features.misc_regs, "miscsr"),
+    ]
+    .into_iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, name)| name)
+    .collect()
+}
+
+/// The `target-feature` string enabling each of the LLVM features
+/// corresponding to `features`, such as `+density,+windowed,...`.
+pub fn target_features(features: &CoreFeatures) -> String {
+    llvm_features(features)
+        .iter()
+        .map(|name| format!("+{}", name))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The name of the LLVM CPU corresponding to `chip`.
+pub fn llvm_cpu(chip: Chip) -> &'static str {
+    match chip {
+        Chip::Esp32 => "esp32",
+        Chip::Esp32s2 => "esp32-s2",
+        Chip::Esp32s3 => "esp32-s3",
+        Chip::Esp8266 => "esp8266",
+    }
+}
+
+/// Generate a rustc target specification for `chip`, in JSON, with its keys
+/// in sorted order.
+///
+/// Without the conditional store option there is no compare-and-swap, but
+/// aligned 32-bit loads and stores are still atomic, so atomics are lowered
+/// to them using the `forced-atomics` feature.
+pub fn target_json(chip: Chip, features: &CoreFeatures) -> String {
+    let mut target_features = target_features(features);
+    if !features.s32c1i {
+        if !target_features.is_empty() {
+            target_features.push(',');
+        }
+        target_features.push_str("+forced-atomics");
+    }
+
+    let spec = json!({
+        "arch": "xtensa",
+        "atomic-cas": features.s32c1i,
+        "cpu": llvm_cpu(chip),
+        "data-layout": "e-m:e-p:32:32-v1:8:8-i64:64-i128:128-n32",
+        "features": target_features,
+        "linker-flavor": "gcc",
+        "llvm-target": "xtensa-none-elf",
+        "max-atomic-width": 32,
+        "panic-strategy": "abort",
+        "target-c-int-width": "32",
+        "target-endian": if features.be { "big" } else { "little" },
+        "target-pointer-width": "32",
+    });
+
+    serde_json::to_string_pretty(&spec).expect("Serializing a JSON value cannot fail") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    const HEADER: &str = "
+        #define XCHAL_HAVE_DENSITY 1
+        #define XCHAL_HAVE_WINDOWED 1
+        #define XCHAL_HAVE_LOOPS 1
+        #define XCHAL_HAVE_FP 1
+        #define XCHAL_HAVE_S32C1I 1
+        #define XCHAL_HAVE_DEBUG 1
+        #define XCHAL_HAVE_INTERRUPTS 1
+        #define XCHAL_HAVE_VECBASE 1
+        #define XCHAL_HAVE_XLT_CACHEATTR 1
+        #define XCHAL_HAVE_MIMIC_CACHEATTR 0
+        #define XCHAL_USE_MEMCTL 1
+        #define XCHAL_NUM_TIMERS 3
+        #define XCHAL_NUM_MISC_REGS 0
+    ";
+
+    fn features(source: &str) -> CoreFeatures {
+        CoreFeatures::from_defines(&parse_str_defines(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn feature_string() {
+        assert_eq!(
+            target_features(&features(HEADER)),
+            "+density,+windowed,+loop,+fp,+s32c1i,+atomctl,+memctl,+debug,+interrupt,+rvector,\
+             +timerint,+regprotect"
+        );
+        assert_eq!(target_features(&CoreFeatures::default()), "");
+    }
+
+    #[test]
+    fn atomics() {
+        let spec = target_json(Chip::Esp32, &features(HEADER));
+        let spec: serde_json::Value = serde_json::from_str(&spec).unwrap();
+        assert_eq!(spec["cpu"], "esp32");
+        assert_eq!(spec["atomic-cas"], true);
+        assert_eq!(spec["max-atomic-width"], 32);
+
+        // As in the upstream xtensa-esp32s2-none-elf target.
+        let header = HEADER.replace("XCHAL_HAVE_S32C1I 1", "XCHAL_HAVE_S32C1I 0");
+        let spec = target_json(Chip::Esp32s2, &features(&header));
+        let spec: serde_json::Value = serde_json::from_str(&spec).unwrap();
+        assert_eq!(spec["cpu"], "esp32-s2");
+        assert_eq!(spec["atomic-cas"], false);
+        assert_eq!(spec["max-atomic-width"], 32);
+
+        let features = spec["features"].as_str().unwrap();
+        assert!(features.ends_with(",+timerint,+regprotect,+forced-atomics"));
+        assert!(!features.contains("atomctl"));
+    }
+
+    #[test]
+    fn sorted_keys() {
+        let spec = target_json(Chip::Esp32s3, &CoreFeatures::default());
+        let keys = spec
+            .lines()
+            .filter_map(|line| line.strip_prefix("  \""))
+            .map(|line| line.split('"').next().unwrap())
+            .collect::<Vec<_>>();
+
+        let mut sorted = keys.clone();
+        sorted.sort_unstable();
+        assert_eq!(keys, sorted);
+        assert!(spec.contains("  \"cpu\": \"esp32-s3\",\n"));
+        assert!(spec.contains("  \"features\": \"+forced-atomics\",\n"));
+        assert!(spec.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+
+use crate::{Chip, Vector, VectorKind, VectorTable};
+
+/// Generate a GNU as source file laying out the vectors of `chip`, which are
+/// at an offset from `VECBASE`, in a `.vectors` section.
+///
+/// Each vector is placed at its offset using `.org`, and given a global label
+/// (such as `_Level2InterruptVector`) followed by a comment noting the space
+/// available before the next vector. The section is aligned to the smallest
+/// power of two covering every offset. The reset vectors are not included, as
+/// they are not placed relative to `VECBASE`.
+///
+/// Fails if two vectors share an offset.
+pub fn assembly_vectors(chip: Chip, table: &VectorTable) -> Result<String> {
+    let mut vectors = table
+        .vectors
+        .iter()
+        .filter(|vector| vector.offset.is_some())
+        .collect::<Vec<_>>();
+    vectors.sort_by_key(|vector| vector.offset);
+
+    for pair in vectors.windows(2) {
+        if pair[0].offset == pair[1].offset {
+            bail!(
+                "The {:?} and {:?} vectors are both at offset {:#x}",
+                pair[0].kind,
+                pair[1].kind,
+                offset(pair[0])
+            );
+        }
+    }
+
+    let alignment = vectors
+        .last()
+        .map_or(1, |vector| (offset(vector) + 1).next_power_of_two());
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "/* Generated by xtensa-core-isa from the `{}` overlay. Do not edit. */",
+        chip
+    )?;
+    writeln!(out)?;
+    writeln!(out, "    .section .vectors, \"ax\"")?;
+    writeln!(out, "    .balign {:#x}", alignment)?;
+    writeln!(out, "    .global _vector_table")?;
+    writeln!(out, "_vector_table:")?;
+
+    for (i, vector) in vectors.iter().enumerate() {
+        writeln!(out)?;
+        writeln!(out, "    .org {:#05x}", offset(vector))?;
+        writeln!(out, "    .global {}", label(vector.kind))?;
+        writeln!(out, "{}:", label(vector.kind))?;
+        match vectors.get(i + 1) {
+            Some(next) => writeln!(
+                out,
+                "    /* {:#x} bytes available */",
+                offset(next) - offset(vector)
+            )?,
+            None => writeln!(out, "    /* Last vector */")?,
+        }
+    }
+
+    Ok(out)
+}
+
+fn offset(vector: &Vector) -> u32 {
+    vector.offset.unwrap_or_default()
+}
+
+/// The label of a vector, following the names used by the Xtensa runtime.
+fn label(kind: VectorKind) -> String {
+    match kind {
+        VectorKind::Reset => "_ResetVector".to_string(),
+        VectorKind::PrimaryReset => "_PrimaryResetVector".to_string(),
+        VectorKind::AlternateReset => "_AlternateResetVector".to_string(),
+        VectorKind::WindowOverflow4 => "_WindowOverflow4".to_string(),
+        VectorKind::WindowUnderflow4 => "_WindowUnderflow4".to_string(),
+        VectorKind::WindowOverflow8 => "_WindowOverflow8".to_string(),
+        VectorKind::WindowUnderflow8 => "_WindowUnderflow8".to_string(),
+        VectorKind::WindowOverflow12 => "_WindowOverflow12".to_string(),
+        VectorKind::WindowUnderflow12 => "_WindowUnderflow12".to_string(),
+        VectorKind::Level(level) => format!("_Level{}InterruptVector", level),
+        VectorKind::Debug => "_DebugExceptionVector".to_string(),
+        VectorKind::Nmi => "_NMIExceptionVector".to_string(),
+        VectorKind::Kernel => "_KernelExceptionVector".to_string(),
+        VectorKind::User => "_UserExceptionVector".to_string(),
+        VectorKind::DoubleException => "_DoubleExceptionVector".to_string(),
+        VectorKind::MemoryError => "_MemoryExceptionVector".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(vectors: &[(VectorKind, Option<u32>)]) -> VectorTable {
+        VectorTable {
+            vecbase: Some(0x4000_0000),
+            vectors: vectors
+                .iter()
+                .map(|(kind, offset)| Vector {
+                    kind: *kind,
+                    offset: *offset,
+                    vaddr: 0x4000_0000 + offset.unwrap_or(0x400),
+                    relocatable: offset.is_some(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let table = table(&[
+            (VectorKind::Reset, None),
+            (VectorKind::PrimaryReset, None),
+            (VectorKind::Kernel, Some(0x300)),
+            (VectorKind::Level(2), Some(0x180)),
+        ]);
+        let out = assembly_vectors(Chip::Esp32, &table).unwrap();
+
+        let lines = out.lines().skip(2).collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "    .section .vectors, \"ax\"",
+                "    .balign 0x400",
+                "    .global _vector_table",
+                "_vector_table:",
+                "",
+                "    .org 0x180",
+                "    .global _Level2InterruptVector",
+                "_Level2InterruptVector:",
+                "    /* 0x180 bytes available */",
+                "",
+                "    .org 0x300",
+                "    .global _KernelExceptionVector",
+                "_KernelExceptionVector:",
+                "    /* Last vector */",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_offsets() {
+        let table = table(&[
+            (VectorKind::Level(6), Some(0x300)),
+            (VectorKind::Debug, Some(0x300)),
+        ]);
+        let err = assembly_vectors(Chip::Esp32, &table).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The Level(6) and Debug vectors are both at offset 0x300"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! A typed model of the interrupts described by the `XCHAL_INT*_` definitions.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::{integer, InterruptType, Value};
+
+/// A single interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct Interrupt {
+    /// The interrupt number, which is also its bit in the `INTERRUPT` and
+    /// `INTENABLE` registers.
+    pub number: u32,
+    /// The priority level of the interrupt.
+    pub level: u32,
+    pub kind: InterruptType,
+}
+
+impl Interrupt {
+    pub fn mask(&self) -> u32 {
+        1 << self.number
+    }
+}
+
+/// All interrupts of a chip, ordered by number.
+///
+/// ```no_run
+/// use xtensa_core_isa::{parse_defines, Chip, InterruptTable, InterruptType};
+///
+/// let table = InterruptTable::from_defines(&parse_defines(Chip::Esp32)?)?;
+/// println!("{:#010x}", table.type_mask(InterruptType::Timer));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InterruptTable {
+    pub interrupts: Vec<Interrupt>,
+}
+
+impl InterruptTable {
+    /// Assemble the table from the `XCHAL_NUM_INTERRUPTS`, `XCHAL_INTn_LEVEL`
+    /// and `XCHAL_INTn_TYPE` definitions.
+    pub fn from_defines(defines: &HashMap<String, Value>) -> Result<Self> {
+        let count = integer(defines, "XCHAL_NUM_INTERRUPTS")?;
+        if !(0..=32).contains(&count) {
+            bail!("Unsupported number of interrupts: {}", count);
+        }
+
+        let interrupts = (0..count as u32)
+            .map(|number| {
+                let level = integer(defines, &format!("XCHAL_INT{}_LEVEL", number))?;
+                let kind = match defines.get(&format!("XCHAL_INT{}_TYPE", number)) {
+                    Some(Value::Interrupt(kind)) => *kind,
+                    _ => bail!("Missing or invalid type for interrupt {}", number),
+                };
+
+                Ok(Interrupt {
+                    number,
+                    level: level as u32,
+                    kind,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { interrupts })
+    }
+
+    pub fn get(&self, number: u32) -> Option<&Interrupt> {
+        self.interrupts
+            .iter()
+            .find(|interrupt| interrupt.number == number)
+    }
+
+    /// The distinct priority levels of the interrupts, in ascending order.
+    pub fn levels(&self) -> Vec<u32> {
+        let mut levels = self
+            .interrupts
+            .iter()
+            .map(|interrupt| interrupt.level)
+            .collect::<Vec<_>>();
+        levels.sort_unstable();
+        levels.dedup();
+
+        levels
+    }
+
+    /// The mask of all interrupts with the given priority level.
+    pub fn level_mask(&self, level: u32) -> u32 {
+        self.mask(|interrupt| interrupt.level == level)
+    }
+
+    /// The mask of all interrupts of the given type.
+    pub fn type_mask(&self, kind: InterruptType) -> u32 {
+        self.mask(|interrupt| interrupt.kind == kind)
+    }
+
+    /// The masks of all interrupts of each priority level, in ascending order
+    /// of level.
+    pub fn level_masks(&self) -> Vec<(u32, u32)> {
+        self.levels()
+            .into_iter()
+            .map(|level| (level, self.level_mask(level)))
+            .collect()
+    }
+
+    /// The masks of all interrupts of each type which is present.
+    pub fn type_masks(&self) -> Vec<(InterruptType, u32)> {
+        InterruptType::iter()
+            .map(|kind| (kind, self.type_mask(kind)))
+            .filter(|(_, mask)| *mask != 0)
+            .collect()
+    }
+
+    fn mask(&self, predicate: impl Fn(&Interrupt) -> bool) -> u32 {
+        self.interrupts
+            .iter()
+            .filter(|interrupt| predicate(interrupt))
+            .fold(0, |mask, interrupt| mask | interrupt.mask())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    const HEADER: &str = "
+        #define XCHAL_NUM_INTERRUPTS 5
+        #define XCHAL_INT0_LEVEL 1
+        #define XCHAL_INT0_TYPE XTHAL_INTTYPE_EXTERN_LEVEL
+        #define XCHAL_INT1_LEVEL 1
+        #define XCHAL_INT1_TYPE XTHAL_INTTYPE_TIMER
+        #define XCHAL_INT2_LEVEL 3
+        #define XCHAL_INT2_TYPE XTHAL_INTTYPE_TIMER
+        #define XCHAL_INT3_LEVEL 3
+        #define XCHAL_INT3_TYPE XTHAL_INTTYPE_SOFTWARE
+        #define XCHAL_INT4_LEVEL 7
+        #define XCHAL_INT4_TYPE XTHAL_INTTYPE_NMI
+    ";
+
+    #[test]
+    fn masks() {
+        let table = InterruptTable::from_defines(&parse_str_defines(HEADER).unwrap()).unwrap();
+
+        assert_eq!(table.get(2).unwrap().mask(), 0b00100);
+        assert_eq!(table.levels(), [1, 3, 7]);
+        assert_eq!(
+            table.level_masks(),
+            [(1, 0b00011), (3, 0b01100), (7, 0b10000)]
+        );
+        assert_eq!(table.level_mask(2), 0);
+        assert_eq!(table.type_mask(InterruptType::Timer), 0b00110);
+        assert_eq!(
+            table.type_masks(),
+            [
+                (InterruptType::ExternLevel, 0b00001),
+                (InterruptType::Nmi, 0b10000),
+                (InterruptType::Software, 0b01000),
+                (InterruptType::Timer, 0b00110),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_type() {
+        let header = HEADER.replace("#define XCHAL_INT3_TYPE XTHAL_INTTYPE_SOFTWARE", "");
+        let err = InterruptTable::from_defines(&parse_str_defines(&header).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "Missing or invalid type for interrupt 3");
+    }
+}
@@ -0,0 +1,305 @@
+//! The front-end of the preprocessor: splitting source into logical lines, and
+//! lines into preprocessing tokens.
+
+use anyhow::{bail, Result};
+
+/// Split `source` into logical lines, as in the first phases of translation:
+///
+/// - a backslash at the end of a line splices it with the line which follows,
+/// - each `/* ... */` comment, which may span multiple lines, is replaced by a
+///   single space, and
+/// - `// ...` comments are removed.
+///
+/// Comment delimiters within string and character literals are preserved.
+/// Each logical line is paired with the (1-based) number of the physical line
+/// on which it starts.
+pub(crate) fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    #[derive(PartialEq)]
+    enum State {
+        Normal,
+        BlockComment,
+        LineComment,
+        Literal(char),
+    }
+
+    // Line splices are removed before anything else is considered, keeping the
+    // number of the physical line on which each remaining character appears.
+    let mut spliced = Vec::new();
+    let mut line = 1;
+    let mut physical = source.chars().peekable();
+    while let Some(c) = physical.next() {
+        if c == '\\' {
+            let mut lookahead = physical.clone();
+            if lookahead.peek() == Some(&'\r') {
+                lookahead.next();
+            }
+            if lookahead.next() == Some('\n') {
+                physical = lookahead;
+                line += 1;
+                continue;
+            }
+        }
+
+        if c == '\r' && physical.peek() == Some(&'\n') {
+            continue;
+        }
+
+        spliced.push((c, line));
+        if c == '\n' {
+            line += 1;
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut state = State::Normal;
+
+    let mut chars = spliced.into_iter().peekable();
+    while let Some((c, line)) = chars.next() {
+        let next = chars.peek().map(|(c, _)| *c);
+
+        match state {
+            State::BlockComment => {
+                if c == '*' && next == Some('/') {
+                    chars.next();
+                    state = State::Normal;
+                }
+                continue;
+            }
+            _ if c == '\n' => {
+                // Unterminated literals end along with the line.
+                lines.push((start_line, std::mem::take(&mut current)));
+                start_line = line + 1;
+                state = State::Normal;
+                continue;
+            }
+            State::LineComment => continue,
+            State::Literal(quote) => {
+                current.push(c);
+                if c == '\\' && next != Some('\n') {
+                    current.extend(chars.next().map(|(c, _)| c));
+                } else if c == quote {
+                    state = State::Normal;
+                }
+                continue;
+            }
+            State::Normal => {}
+        }
+
+        match (c, next) {
+            ('/', Some('*')) => {
+                chars.next();
+                current.push(' ');
+                state = State::BlockComment;
+            }
+            ('/', Some('/')) => state = State::LineComment,
+            ('"' | '\'', _) => {
+                current.push(c);
+                state = State::Literal(c);
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        lines.push((start_line, current));
+    }
+
+    lines
+}
+
+/// The kind of a preprocessing token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Identifier,
+    Number,
+    String,
+    Character,
+    Punct,
+}
+
+/// A preprocessing token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) text: String,
+    /// Whether the token is preceded by whitespace, which is significant when
+    /// stringifying macro arguments and when distinguishing function-like macro
+    /// definitions.
+    pub(crate) space_before: bool,
+}
+
+impl Token {
+    pub(crate) fn is_identifier(&self, identifier: &str) -> bool {
+        self.kind == TokenKind::Identifier && self.text == identifier
+    }
+
+    pub(crate) fn is_punct(&self, punct: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == punct
+    }
+}
+
+// Longer punctuators must precede any of their prefixes.
+const PUNCTUATORS: &[&str] = &[
+    "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*=",
+    "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "[", "]", "(", ")", "{", "}", ".", "&", "*",
+    "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":", ";", "=", ",", "#",
+];
+
+/// Split `text` into preprocessing tokens.
+pub(crate) fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    loop {
+        let trimmed = rest.trim_start();
+        let space_before = trimmed.len() != rest.len();
+        rest = trimmed;
+
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+
+        let (kind, len) = if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            (TokenKind::Identifier, len)
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            (TokenKind::Number, number_len(rest))
+        } else if c == '"' || c == '\'' {
+            let kind = if c == '"' {
+                TokenKind::String
+            } else {
+                TokenKind::Character
+            };
+            (kind, quoted_len(rest)?)
+        } else if let Some(punct) = PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+            (TokenKind::Punct, punct.len())
+        } else {
+            bail!("Unexpected character '{}'", c);
+        };
+
+        tokens.push(Token {
+            kind,
+            text: rest[..len].to_string(),
+            space_before,
+        });
+        rest = &rest[len..];
+    }
+
+    Ok(tokens)
+}
+
+/// The length of the preprocessing number at the start of `text`, which
+/// includes any suffix as well as exponents such as `1e+5`.
+fn number_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut len = 1;
+
+    while len < bytes.len() {
+        let c = bytes[len];
+        let exponent_sign =
+            matches!(c, b'+' | b'-') && matches!(bytes[len - 1], b'e' | b'E' | b'p' | b'P');
+        if !(exponent_sign || c.is_ascii_alphanumeric() || c == b'_' || c == b'.') {
+            break;
+        }
+        len += 1;
+    }
+
+    len
+}
+
+/// The length of the string or character literal at the start of `text`,
+/// including its quotes.
+fn quoted_len(text: &str) -> Result<usize> {
+    let quote = text.as_bytes()[0];
+    let mut escaped = false;
+
+    for (index, c) in text.bytes().enumerate().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            _ if c == quote => return Ok(index + 1),
+            _ => {}
+        }
+    }
+
+    bail!("Unterminated literal: {}", text)
+}
+
+/// Join `tokens` back into text, separating tokens which were preceded by
+/// whitespace with a single space.
+pub(crate) fn join(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for token in tokens {
+        if token.space_before && !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&token.text);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_lines(source: &str, expected: &[(usize, &str)]) {
+        let lines = logical_lines(source);
+        let lines = lines
+            .iter()
+            .map(|(line, text)| (*line, text.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn splices() {
+        assert_lines(
+            "#define A \\\n  1\n#define B 2\n",
+            &[(1, "#define A   1"), (3, "#define B 2")],
+        );
+    }
+
+    #[test]
+    fn splice_inside_block_comment() {
+        assert_lines(
+            "#define A /* one \\\n two */ 1\n#define B 2",
+            &[(1, "#define A   1"), (3, "#define B 2")],
+        );
+        // A splice also joins the two halves of a comment delimiter.
+        assert_lines("A /\\\n* B *\\\n/ C", &[(1, "A   C")]);
+    }
+
+    #[test]
+    fn line_numbers_after_block_comments() {
+        let source = "/*\n * Copyright\n *\n */\n#define A 1 /* a\n b */\n#define B 2\n";
+        assert_lines(
+            source,
+            &[(1, " "), (5, "#define A 1  "), (7, "#define B 2")],
+        );
+    }
+
+    #[test]
+    fn crlf() {
+        assert_lines(
+            "#define A \\\r\n 1\r\n// comment\r\n#define B 2\r\n",
+            &[(1, "#define A  1"), (3, ""), (4, "#define B 2")],
+        );
+    }
+
+    #[test]
+    fn comments_in_literals() {
+        assert_lines(
+            "#define S \"/* x */\" // y\n#define C '/'/* z */",
+            &[(1, "#define S \"/* x */\" "), (2, "#define C '/' ")],
+        );
+    }
+}
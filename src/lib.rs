@@ -0,0 +1,558 @@
+//! Parse the `core-isa.h` configuration headers for Xtensa-based Espressif
+//! chips.
+//!
+//! Each chip's header is read from the corresponding overlay in the
+//! `xtensa-overlays` submodule, and every `#define` is mapped from its
+//! identifier to a [Value]. Headers from elsewhere can be parsed using
+//! [parse_file_defines] or [parse_str_defines]. Definitions which cannot be
+//! resolved are reported as [Diagnostic]s rather than causing parsing to fail,
+//! unless a strict [Parser] is used.
+//!
+//! Only definitions within the active branches of conditional directives are
+//! collected, and `#include` directives are followed. Use a [Parser] to
+//! control which macros are predefined when evaluating these conditions, and
+//! where included headers are searched for.
+//!
+//! The replacement text of each definition is expanded, including any
+//! invocations of function-like macros, and then evaluated as a C integer
+//! constant expression; string literals and interrupt types are also
+//! recognized.
+//!
+//! ```no_run
+//! use xtensa_core_isa::{parse_defines, Chip};
+//!
+//! let config = parse_defines(Chip::Esp32s3)?;
+//! println!("{:?}", config.get("XCHAL_NUM_INTERRUPTS"));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use enum_as_inner::EnumAsInner;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+
+pub use crate::{
+    cache::{CacheConfig, CacheKind, Caches},
+    config::{CoreConfig, Definition, Location, Resolution, ResolutionStep},
+    diagnostics::{Diagnostic, DiagnosticKind, Severity},
+    diff::{Category, CategoryDiff, ChangedValue, Diff},
+    features::{CoreFeatures, FeatureComparison},
+    format::Format,
+    interrupts::{Interrupt, InterruptTable},
+    memory::{EccParity, MemoryKind, MemoryMap, MemoryRegion},
+    models::CoreModels,
+    vectors::{Vector, VectorKind, VectorTable},
+};
+use crate::{lexer::TokenKind, preprocessor::Preprocessor};
+
+mod cache;
+mod config;
+mod diagnostics;
+mod diff;
+mod expr;
+mod features;
+mod format;
+pub mod generate;
+mod interrupts;
+mod lexer;
+mod literal;
+mod macros;
+mod memory;
+mod models;
+mod preprocessor;
+mod vectors;
+
+// Note that for the ESP32, since we are not using an RTOS we need to use the
+// 'xtensa_esp108' overlay instead of the 'xtensa_esp32' overlay.
+// https://docs.espressif.com/projects/esp-idf/en/v3.3.5/api-guides/jtag-debugging/tips-and-quirks.html
+/// The chips whose configuration can be parsed.
+///
+/// The [Display] implementation yields the name of the chip's overlay, while
+/// it is serialized using its [Chip::name].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chip {
+    #[strum(to_string = "xtensa_esp108")]
+    Esp32,
+    #[strum(to_string = "xtensa_esp32s2")]
+    Esp32s2,
+    #[strum(to_string = "xtensa_esp32s3")]
+    Esp32s3,
+    #[strum(to_string = "xtensa_lx106")]
+    Esp8266,
+}
+
+impl Chip {
+    /// The name of the chip, in lowercase, such as `esp32s3`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chip::Esp32 => "esp32",
+            Chip::Esp32s2 => "esp32s2",
+            Chip::Esp32s3 => "esp32s3",
+            Chip::Esp8266 => "esp8266",
+        }
+    }
+
+    /// The path to the chip's `core-isa.h` file within the overlays submodule.
+    pub fn core_isa_path(&self) -> Result<PathBuf> {
+        let path = self
+            .include_dir()
+            .join("xtensa/config/core-isa.h")
+            .canonicalize()
+            .with_context(|| {
+                format!(
+                    "Unable to find the header of the {:?}; is the xtensa-overlays submodule checked out?",
+                    self
+                )
+            })?;
+
+        Ok(path)
+    }
+
+    /// The directory within the overlays submodule from which the chip's
+    /// `xtensa/...` headers are included.
+    pub fn include_dir(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("xtensa-overlays")
+            .join(self.to_string())
+            .join("newlib/newlib/libc/sys/xtensa/include")
+    }
+
+    /// A [Parser] predefining the macros which the compiler would define when
+    /// targeting the chip.
+    ///
+    /// All chips are little-endian; the ESP8266 uses the CALL0 ABI, while the
+    /// others use the windowed ABI.
+    ///
+    /// As `core-isa.h` refers to the hardware version constants from
+    /// `xtensa/hal.h` without including it, this header is included
+    /// beforehand. The subset of it bundled with this crate takes precedence
+    /// over the chip's [Chip::include_dir], whose full `xtensa/hal.h` would
+    /// itself include `core-isa.h`.
+    pub fn parser(&self) -> Parser {
+        let parser = Parser::new()
+            .include_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("include"))
+            .include_dir(self.include_dir())
+            .prelude("#include <xtensa/hal.h>")
+            .define("__XTENSA__", "1")
+            .define("__xtensa__", "1")
+            .define("__XTENSA_EL__", "1");
+
+        match self {
+            Chip::Esp8266 => parser.define("__XTENSA_CALL0_ABI__", "1"),
+            _ => parser.define("__XTENSA_WINDOWED_ABI__", "1"),
+        }
+    }
+}
+
+impl FromStr for Chip {
+    type Err = anyhow::Error;
+
+    /// Parse a chip from either its [Chip::name] or the name of its overlay.
+    fn from_str(s: &str) -> Result<Self> {
+        match Chip::iter().find(|chip| chip.name() == s || chip.to_string() == s) {
+            Some(chip) => Ok(chip),
+            None => bail!("Unknown chip: {}", s),
+        }
+    }
+}
+
+/// The type of an interrupt, as given by the `XCHAL_INT*_TYPE` definitions.
+///
+/// Interrupt types are displayed and serialized using the names of their
+/// definitions.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, EnumString, Serialize, Deserialize,
+)]
+pub enum InterruptType {
+    #[strum(serialize = "XTHAL_INTTYPE_EXTERN_EDGE")]
+    #[serde(rename = "XTHAL_INTTYPE_EXTERN_EDGE")]
+    ExternEdge,
+    #[strum(serialize = "XTHAL_INTTYPE_EXTERN_LEVEL")]
+    #[serde(rename = "XTHAL_INTTYPE_EXTERN_LEVEL")]
+    ExternLevel,
+    #[strum(serialize = "XTHAL_INTTYPE_NMI")]
+    #[serde(rename = "XTHAL_INTTYPE_NMI")]
+    Nmi,
+    #[strum(serialize = "XTHAL_INTTYPE_PROFILING")]
+    #[serde(rename = "XTHAL_INTTYPE_PROFILING")]
+    Profiling,
+    #[strum(serialize = "XTHAL_INTTYPE_SOFTWARE")]
+    #[serde(rename = "XTHAL_INTTYPE_SOFTWARE")]
+    Software,
+    #[strum(serialize = "XTHAL_INTTYPE_TIMER")]
+    #[serde(rename = "XTHAL_INTTYPE_TIMER")]
+    Timer,
+    #[strum(serialize = "XTHAL_TIMER_UNCONFIGURED")]
+    #[serde(rename = "XTHAL_TIMER_UNCONFIGURED")]
+    TimerUnconfigured,
+}
+
+/// The value of a single definition.
+///
+/// Values are serialized as a plain number or string.
+#[derive(Debug, Clone, PartialEq, EnumAsInner, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Integer(i64),
+    Interrupt(InterruptType),
+    String(String),
+}
+
+impl fmt::Display for Value {
+    /// Formats integers in decimal, interrupt types by the names of their
+    /// definitions, and strings as quoted literals.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Interrupt(interrupt) => write!(f, "{}", interrupt),
+            Value::String(string) => write!(f, "{:?}", string),
+        }
+    }
+}
+
+/// The macros which are expanded in resolving a definition, both object-like
+/// and function-like, in the order in which each was first expanded.
+fn resolved_through(
+    preprocessor: &Preprocessor,
+    identifier: &str,
+    body: &str,
+) -> Vec<ResolutionStep> {
+    let expanded = match preprocessor.expand_traced(body) {
+        Ok((_, expanded)) => expanded,
+        Err(_) => return Vec::new(),
+    };
+
+    expanded
+        .iter()
+        .filter(|name| *name != identifier)
+        .filter_map(|name| {
+            let m = preprocessor.get(name)?;
+            Some(ResolutionStep {
+                identifier: name.clone(),
+                raw: m.body.trim().to_string(),
+                location: m.location.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The value of an integer definition.
+pub(crate) fn integer(defines: &HashMap<String, Value>, identifier: &str) -> Result<i64> {
+    match defines.get(identifier) {
+        Some(Value::Integer(integer)) => Ok(*integer),
+        Some(value) => bail!(
+            "Definition of {} is not an integer: {:?}",
+            identifier,
+            value
+        ),
+        None => bail!("Missing definition: {}", identifier),
+    }
+}
+
+/// The value of a boolean definition, such as `XCHAL_HAVE_*`, treating a
+/// missing definition as false.
+pub(crate) fn flag(defines: &HashMap<String, Value>, identifier: &str) -> Result<bool> {
+    match defines.get(identifier) {
+        Some(Value::Integer(integer)) => Ok(*integer != 0),
+        Some(value) => bail!(
+            "Definition of {} is not an integer: {:?}",
+            identifier,
+            value
+        ),
+        None => Ok(false),
+    }
+}
+
+/// Parse the definitions for a chip from its `core-isa.h` file, using the
+/// chip's [Chip::parser].
+pub fn parse_defines(chip: Chip) -> Result<HashMap<String, Value>> {
+    Ok(parse_config(chip)?.to_map())
+}
+
+/// Parse the definitions for a chip from its `core-isa.h` file, along with
+/// their locations and raw replacement text.
+pub fn parse_config(chip: Chip) -> Result<CoreConfig> {
+    chip.parser().parse_file(chip.core_isa_path()?)
+}
+
+/// Parse the definitions from the `core-isa.h` file located at `path`, without
+/// any predefined macros.
+pub fn parse_file_defines(path: impl AsRef<Path>) -> Result<HashMap<String, Value>> {
+    Ok(Parser::new().parse_file(path)?.to_map())
+}
+
+/// Parse the definitions from the contents of a `core-isa.h` file, without any
+/// predefined macros.
+pub fn parse_str_defines(source: &str) -> Result<HashMap<String, Value>> {
+    Ok(Parser::new().parse_str(source)?.to_map())
+}
+
+/// A configurable parser for `core-isa.h` headers.
+///
+/// ```
+/// use xtensa_core_isa::Parser;
+///
+/// let config = Parser::new()
+///     .define("__XTENSA_CALL0_ABI__", "1")
+///     .parse_str("#ifdef __XTENSA_CALL0_ABI__\n#define ABI 0\n#else\n#define ABI 1\n#endif")?;
+/// assert_eq!(config["ABI"].as_integer(), Some(&0));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    predefined: Vec<(String, String)>,
+    preludes: Vec<String>,
+    include_dirs: Vec<PathBuf>,
+    strict: bool,
+}
+
+impl Parser {
+    /// Create a parser without any predefined macros.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Predefine the macro `identifier`, as if by `#define identifier value`.
+    ///
+    /// Predefined macros are taken into account when evaluating conditional
+    /// directives and resolving identifiers, but are not themselves included
+    /// in the parsed definitions.
+    pub fn define(mut self, identifier: impl Into<String>, value: impl Into<String>) -> Self {
+        self.predefined.push((identifier.into(), value.into()));
+        self
+    }
+
+    /// Add a directory to search for headers named by `#include` directives.
+    ///
+    /// Directories are searched in the order in which they were added. Headers
+    /// named using quotes are first searched for relative to the including
+    /// header.
+    pub fn include_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+
+    /// Process `source` before the header, as if it were included at the very
+    /// start of it (similar to the compiler's `-include` option).
+    ///
+    /// As with predefined macros, the definitions made by a prelude are not
+    /// themselves included in the parsed definitions.
+    pub fn prelude(mut self, source: impl Into<String>) -> Self {
+        self.preludes.push(source.into());
+        self
+    }
+
+    /// In strict mode, parsing fails if any diagnostic with a severity of
+    /// [Severity::Error] is reported, such as for a definition which cannot be
+    /// resolved. Otherwise, such definitions are omitted from the result and
+    /// only reported in [CoreConfig::diagnostics].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Parse the definitions from the header located at `path`, including
+    /// those from any headers which it includes.
+    pub fn parse_file(&self, path: impl AsRef<Path>) -> Result<CoreConfig> {
+        let path = path.as_ref();
+        let mut preprocessor = self.preprocessor()?;
+        preprocessor
+            .process_file(path, false)
+            .with_context(|| format!("Unable to parse header: {}", path.display()))?;
+
+        self.evaluate(&preprocessor)
+    }
+
+    /// Parse the definitions from the contents of a header, including those
+    /// from any headers which it includes.
+    pub fn parse_str(&self, source: &str) -> Result<CoreConfig> {
+        let mut preprocessor = self.preprocessor()?;
+        preprocessor.process(source, None, false)?;
+
+        self.evaluate(&preprocessor)
+    }
+
+    /// Create a preprocessor which has processed the predefined macros and
+    /// preludes.
+    fn preprocessor(&self) -> Result<Preprocessor> {
+        let mut preprocessor = Preprocessor::new(&self.predefined, &self.include_dirs)?;
+        for prelude in &self.preludes {
+            preprocessor
+                .process(prelude, None, true)
+                .context("Unable to process prelude")?;
+        }
+
+        Ok(preprocessor)
+    }
+
+    /// Evaluate the definitions collected by the preprocessor.
+    fn evaluate(&self, preprocessor: &Preprocessor) -> Result<CoreConfig> {
+        let re_ident = Regex::new(r"^[a-zA-Z_][a-zA-Z\d_]*$")?;
+
+        // Iterate through each definition in an active branch, mapping identifiers
+        // to values. Predefined macros may be referred to, but are not included in
+        // the result.
+        let mut map: HashMap<String, Value> = HashMap::new();
+        let mut config = CoreConfig::default();
+        config.diagnostics = preprocessor.diagnostics().to_vec();
+
+        for (identifier, m) in preprocessor.definitions() {
+            // Function-like macros are only used in the expansion of other
+            // definitions.
+            if m.is_function_like() {
+                continue;
+            }
+
+            let body = m.body.trim();
+            let diagnostic = |kind, reason| Diagnostic {
+                kind,
+                identifier: identifier.to_string(),
+                location: m.location.clone(),
+                reason,
+            };
+
+            if body.is_empty() {
+                if !m.predefined && !preprocessor.is_include_guard(identifier) {
+                    let reason = String::from("Definition has no replacement text");
+                    config
+                        .diagnostics
+                        .push(diagnostic(DiagnosticKind::EmptyDefinition, reason));
+                }
+                continue;
+            }
+
+            let value = if let Ok(interrupt) = InterruptType::from_str(body) {
+                // Interrupt type
+                Value::Interrupt(interrupt)
+            } else if re_ident.is_match(body) && map.contains_key(body) {
+                // Identifier
+                map.get(body).unwrap().to_owned()
+            } else {
+                match self.evaluate_body(preprocessor, &map, body) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        if !m.predefined {
+                            let reason = format!("Unable to evaluate '{}': {:#}", body, err);
+                            config
+                                .diagnostics
+                                .push(diagnostic(DiagnosticKind::UnresolvedDefinition, reason));
+                        }
+                        continue;
+                    }
+                }
+            };
+
+            map.insert(identifier.to_string(), value.clone());
+
+            if let (false, Some(location)) = (m.predefined, &m.location) {
+                config.insert(Definition {
+                    identifier: identifier.to_string(),
+                    raw: body.to_string(),
+                    value,
+                    location: location.clone(),
+                    resolved_through: resolved_through(preprocessor, identifier, body),
+                });
+            }
+        }
+
+        if self.strict {
+            let errors = config
+                .diagnostics()
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>();
+
+            if !errors.is_empty() {
+                bail!("Unable to resolve all definitions:\n{}", errors.join("\n"));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Evaluate the replacement text of a definition once any macros within it
+    /// have been expanded.
+    fn evaluate_body(
+        &self,
+        preprocessor: &Preprocessor,
+        map: &HashMap<String, Value>,
+        body: &str,
+    ) -> Result<Value> {
+        let tokens = preprocessor.expand(body)?;
+
+        let interrupt = match tokens.as_slice() {
+            [token] => InterruptType::from_str(&token.text).ok(),
+            _ => None,
+        };
+
+        let value = if let Some(interrupt) = interrupt {
+            // Interrupt type
+            Value::Interrupt(interrupt)
+        } else if !tokens.is_empty() && tokens.iter().all(|token| token.kind == TokenKind::String) {
+            // String, including any concatenated string literals
+            let string = tokens.iter().map(|token| unquote(&token.text)).collect();
+            Value::String(string)
+        } else {
+            // Integer constant expression
+            let context = Definitions { values: map };
+            Value::Integer(expr::evaluate(&lexer::join(&tokens), &context)?)
+        };
+
+        Ok(value)
+    }
+}
+
+/// Strip the quotes from a string literal, and resolve any simple escape
+/// sequences.
+fn unquote(literal: &str) -> String {
+    let mut string = String::new();
+    let mut chars = literal[1..literal.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some(c) => string.push(c),
+                None => {}
+            },
+            c => string.push(c),
+        }
+    }
+
+    string
+}
+
+/// The context in which the replacement text of definitions is evaluated, once
+/// any macros have been expanded.
+///
+/// Any remaining identifiers resolve to the integer values of previously
+/// parsed definitions.
+struct Definitions<'a> {
+    values: &'a HashMap<String, Value>,
+}
+
+impl expr::Context for Definitions<'_> {
+    fn is_defined(&self, identifier: &str) -> bool {
+        self.values.contains_key(identifier)
+    }
+
+    fn resolve(&self, identifier: &str) -> Result<i64> {
+        match self.values.get(identifier) {
+            Some(Value::Integer(integer)) => Ok(*integer),
+            Some(_) => bail!("Identifier is not an integer: {}", identifier),
+            None => bail!("Unresolved identifier: {}", identifier),
+        }
+    }
+}
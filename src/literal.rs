@@ -0,0 +1,299 @@
+//! Parsing of C integer literals and character constants.
+//!
+//! Literals are typed according to the Xtensa ABI, in which `int` and `long`
+//! are 32 bits wide and `long long` is 64 bits wide.
+
+use anyhow::{anyhow, bail, Result};
+
+/// The type of an integer literal, or of the result of an integer expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IntegerType {
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+}
+
+impl IntegerType {
+    pub(crate) fn bits(&self) -> u32 {
+        match self {
+            IntegerType::LongLong | IntegerType::UnsignedLongLong => 64,
+            _ => 32,
+        }
+    }
+
+    pub(crate) fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            IntegerType::UnsignedInt | IntegerType::UnsignedLong | IntegerType::UnsignedLongLong
+        )
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            IntegerType::Int | IntegerType::UnsignedInt => 0,
+            IntegerType::Long | IntegerType::UnsignedLong => 1,
+            IntegerType::LongLong | IntegerType::UnsignedLongLong => 2,
+        }
+    }
+
+    fn to_unsigned(self) -> Self {
+        match self {
+            IntegerType::Int => IntegerType::UnsignedInt,
+            IntegerType::Long => IntegerType::UnsignedLong,
+            IntegerType::LongLong => IntegerType::UnsignedLongLong,
+            ty => ty,
+        }
+    }
+
+    /// The largest value representable by the type.
+    fn max(&self) -> u64 {
+        match (self.bits(), self.is_unsigned()) {
+            (32, false) => i32::MAX as u64,
+            (32, true) => u32::MAX as u64,
+            (_, false) => i64::MAX as u64,
+            (_, true) => u64::MAX,
+        }
+    }
+
+    /// The common type of the operands of a binary operator, as determined by
+    /// the usual arithmetic conversions.
+    pub(crate) fn common(self, other: Self) -> Self {
+        let (high, low) = if self.rank() >= other.rank() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+
+        if high.is_unsigned() == low.is_unsigned() || high.is_unsigned() {
+            high
+        } else if high.bits() > low.bits() {
+            // The signed type can represent every value of the unsigned type.
+            high
+        } else {
+            high.to_unsigned()
+        }
+    }
+}
+
+/// A parsed integer literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IntegerLiteral {
+    pub(crate) value: u64,
+    pub(crate) ty: IntegerType,
+}
+
+/// Parse a C integer literal: a decimal, octal (`0777`), hexadecimal
+/// (`0x1F`) or binary (`0b101`) number, with an optional `U`, `L`, `UL` or
+/// `ULL` suffix in any case and order.
+///
+/// The type of the literal is the first of the types permitted by its suffix
+/// and radix in which its value can be represented.
+pub(crate) fn parse_integer(literal: &str) -> Result<IntegerLiteral> {
+    let digits_len = literal.trim_end_matches(['u', 'U', 'l', 'L']).len();
+    let (number, suffix) = literal.split_at(digits_len);
+
+    let (digits, radix) = if let Some(hex) = number.strip_prefix("0x").or(number.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if let Some(binary) = number.strip_prefix("0b").or(number.strip_prefix("0B")) {
+        (binary, 2)
+    } else if number.len() > 1 && number.starts_with('0') {
+        (&number[1..], 8)
+    } else {
+        (number, 10)
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        bail!("Invalid integer literal: {}", literal);
+    }
+    let value = match u64::from_str_radix(digits, radix) {
+        Ok(value) => value,
+        Err(_) => bail!("Integer literal is too large: {}", literal),
+    };
+
+    use IntegerType::*;
+    let decimal = radix == 10;
+    let candidates: &[IntegerType] = match suffix {
+        "" if decimal => &[Int, Long, LongLong],
+        "" => &[
+            Int,
+            UnsignedInt,
+            Long,
+            UnsignedLong,
+            LongLong,
+            UnsignedLongLong,
+        ],
+        "u" | "U" => &[UnsignedInt, UnsignedLong, UnsignedLongLong],
+        "l" | "L" if decimal => &[Long, LongLong],
+        "l" | "L" => &[Long, UnsignedLong, LongLong, UnsignedLongLong],
+        "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" => &[UnsignedLong, UnsignedLongLong],
+        "ll" | "LL" if decimal => &[LongLong],
+        "ll" | "LL" => &[LongLong, UnsignedLongLong],
+        "ull" | "uLL" | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => &[UnsignedLongLong],
+        _ => bail!("Invalid integer literal suffix: {}", literal),
+    };
+
+    match candidates.iter().find(|ty| value <= ty.max()) {
+        Some(ty) => Ok(IntegerLiteral { value, ty: *ty }),
+        None => bail!("Integer literal is too large: {}", literal),
+    }
+}
+
+/// Parse a C character constant, such as `'a'`, `'\n'` or `'\x7f'`, which
+/// has type `int`.
+///
+/// As `char` is unsigned in the Xtensa ABI, the value of a constant is that of
+/// its character as an `unsigned char`. Multi-character constants, whose values
+/// are implementation-defined, and wide character constants are not supported.
+pub(crate) fn parse_character(literal: &str) -> Result<IntegerLiteral> {
+    let unsupported = || anyhow!("Unsupported character constant: {}", literal);
+
+    let body = literal
+        .strip_prefix('\'')
+        .and_then(|literal| literal.strip_suffix('\''))
+        .filter(|body| !body.is_empty())
+        .ok_or_else(unsupported)?;
+
+    let (value, rest) = match body.strip_prefix('\\') {
+        Some(escape) => {
+            let c = escape.chars().next().ok_or_else(unsupported)?;
+            let simple = match c {
+                'a' => Some(0x07),
+                'b' => Some(0x08),
+                'f' => Some(0x0c),
+                'n' => Some(0x0a),
+                'r' => Some(0x0d),
+                't' => Some(0x09),
+                'v' => Some(0x0b),
+                '\\' | '\'' | '"' | '?' => Some(c as u64),
+                _ => None,
+            };
+
+            if let Some(value) = simple {
+                (value, &escape[1..])
+            } else {
+                // A hexadecimal escape has any number of digits, and an octal
+                // escape at most three.
+                let (digits, radix) = match escape.strip_prefix('x') {
+                    Some(hex) => (hex, 16),
+                    None => (escape, 8),
+                };
+                let mut len = digits
+                    .find(|c: char| !c.is_digit(radix))
+                    .unwrap_or(digits.len());
+                if radix == 8 {
+                    len = len.min(3);
+                }
+                if len == 0 {
+                    return Err(unsupported());
+                }
+
+                let value = u64::from_str_radix(&digits[..len], radix)
+                    .ok()
+                    .filter(|value| *value <= u8::MAX as u64)
+                    .ok_or_else(|| anyhow!("Escape sequence out of range: {}", literal))?;
+                (value, &digits[len..])
+            }
+        }
+        None => {
+            let c = body.chars().next().unwrap();
+            if !c.is_ascii() {
+                return Err(unsupported());
+            }
+            (c as u64, &body[1..])
+        }
+    };
+
+    if !rest.is_empty() {
+        return Err(unsupported());
+    }
+
+    Ok(IntegerLiteral {
+        value,
+        ty: IntegerType::Int,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IntegerType::*, *};
+
+    fn parse(literal: &str) -> (u64, IntegerType) {
+        let literal = parse_integer(literal).unwrap();
+        (literal.value, literal.ty)
+    }
+
+    #[test]
+    fn unsuffixed_types() {
+        // Hexadecimal literals may be unsigned, while decimal literals may not.
+        assert_eq!(parse("0x7fffffff"), (0x7fff_ffff, Int));
+        assert_eq!(parse("0x80000000"), (0x8000_0000, UnsignedInt));
+        assert_eq!(parse("2147483647"), (0x7fff_ffff, Int));
+        assert_eq!(parse("2147483648"), (0x8000_0000, LongLong));
+        assert_eq!(parse("0x100000000"), (0x1_0000_0000, LongLong));
+        assert_eq!(parse("0xffffffffffffffff"), (u64::MAX, UnsignedLongLong));
+        assert!(parse_integer("18446744073709551615").is_err());
+    }
+
+    #[test]
+    fn radixes() {
+        assert_eq!(parse("0"), (0, Int));
+        assert_eq!(parse("0777"), (0o777, Int));
+        assert_eq!(parse("0b101"), (5, Int));
+        assert_eq!(parse("0XaB"), (0xab, Int));
+
+        assert!(parse_integer("08").is_err());
+        assert!(parse_integer("0x").is_err());
+        assert!(parse_integer("0b2").is_err());
+    }
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(parse("1u"), (1, UnsignedInt));
+        assert_eq!(parse("1L"), (1, Long));
+        assert_eq!(parse("1UL"), (1, UnsignedLong));
+        assert_eq!(parse("1lu"), (1, UnsignedLong));
+        assert_eq!(parse("1LL"), (1, LongLong));
+        assert_eq!(parse("1LLU"), (1, UnsignedLongLong));
+        assert_eq!(parse("1ull"), (1, UnsignedLongLong));
+        assert_eq!(parse("2147483648L"), (0x8000_0000, LongLong));
+        assert_eq!(parse("0x80000000L"), (0x8000_0000, UnsignedLong));
+
+        assert!(parse_integer("1lL").is_err());
+        assert!(parse_integer("1uu").is_err());
+        assert!(parse_integer("1LUL").is_err());
+    }
+
+    #[test]
+    fn common_types() {
+        assert_eq!(Int.common(UnsignedInt), UnsignedInt);
+        assert_eq!(Long.common(UnsignedInt), UnsignedLong);
+        assert_eq!(LongLong.common(UnsignedInt), LongLong);
+        assert_eq!(UnsignedLong.common(LongLong), LongLong);
+        assert_eq!(LongLong.common(UnsignedLongLong), UnsignedLongLong);
+    }
+
+    #[test]
+    fn characters() {
+        let parse = |literal| parse_character(literal).unwrap().value;
+        assert_eq!(parse("'a'"), 0x61);
+        assert_eq!(parse("'\\n'"), 0x0a);
+        assert_eq!(parse("'\\''"), 0x27);
+        assert_eq!(parse("'\\\\'"), 0x5c);
+        assert_eq!(parse("'\\0'"), 0);
+        assert_eq!(parse("'\\101'"), 0x41);
+        assert_eq!(parse("'\\x7f'"), 0x7f);
+        assert_eq!(parse("'\\xff'"), 0xff);
+        assert_eq!(parse_character("'a'").unwrap().ty, Int);
+
+        for literal in ["''", "'ab'", "'\\1234'", "'\\x'", "'\\q'", "'\u{e9}'"] {
+            let err = parse_character(literal).unwrap_err();
+            assert!(err.to_string().contains("Unsupported"), "{}", literal);
+        }
+        let err = parse_character("'\\x100'").unwrap_err();
+        assert!(err.to_string().contains("out of range"));
+    }
+}
@@ -0,0 +1,452 @@
+//! Macro definitions and their expansion.
+//!
+//! Expansion follows the C standard's rescanning rules, using the "hide set"
+//! algorithm to prevent recursive expansion: each token records the macros
+//! whose expansion produced it, which may not be expanded again.
+
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    lexer::{self, Token, TokenKind},
+    Location,
+};
+
+/// A macro definition.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Macro {
+    /// The replacement text of the macro.
+    pub(crate) body: String,
+    /// The tokens of the replacement text.
+    pub(crate) tokens: Vec<Token>,
+    /// The names of the parameters of a function-like macro, ending with
+    /// `__VA_ARGS__` if it is variadic.
+    pub(crate) params: Option<Vec<String>>,
+    /// Whether the macro was supplied by the caller rather than defined in the
+    /// header.
+    pub(crate) predefined: bool,
+    /// Where the macro was defined, unless it was predefined by the caller.
+    pub(crate) location: Option<Location>,
+}
+
+impl Macro {
+    pub(crate) fn new(
+        params: Option<Vec<String>>,
+        body: &str,
+        predefined: bool,
+        location: Option<Location>,
+    ) -> Result<Self> {
+        let mut tokens = lexer::tokenize(body)?;
+        if let Some(token) = tokens.first_mut() {
+            token.space_before = false;
+        }
+
+        if tokens.first().is_some_and(|token| token.is_punct("##"))
+            || tokens.last().is_some_and(|token| token.is_punct("##"))
+        {
+            bail!("'##' cannot appear at either end of a macro expansion");
+        }
+
+        Ok(Self {
+            body: body.to_string(),
+            tokens,
+            params,
+            predefined,
+            location,
+        })
+    }
+
+    pub(crate) fn is_function_like(&self) -> bool {
+        self.params.is_some()
+    }
+
+    fn is_variadic(&self) -> bool {
+        self.params
+            .as_ref()
+            .and_then(|params| params.last())
+            .is_some_and(|param| param == "__VA_ARGS__")
+    }
+
+    fn param_index(&self, token: &Token) -> Option<usize> {
+        if token.kind != TokenKind::Identifier {
+            return None;
+        }
+
+        self.params
+            .as_ref()?
+            .iter()
+            .position(|param| *param == token.text)
+    }
+}
+
+/// A token along with its hide set.
+#[derive(Debug, Clone)]
+struct Item {
+    token: Token,
+    hideset: Vec<String>,
+}
+
+/// Fully expand the macros in `tokens`.
+pub(crate) fn expand(tokens: Vec<Token>, macros: &HashMap<String, Macro>) -> Result<Vec<Token>> {
+    Ok(expand_traced(tokens, macros)?.0)
+}
+
+/// Fully expand the macros in `tokens`, also returning the name of every macro
+/// which was expanded, in the order in which each was first expanded.
+pub(crate) fn expand_traced(
+    tokens: Vec<Token>,
+    macros: &HashMap<String, Macro>,
+) -> Result<(Vec<Token>, Vec<String>)> {
+    let items = tokens
+        .into_iter()
+        .map(|token| Item {
+            token,
+            hideset: Vec::new(),
+        })
+        .collect();
+
+    let mut expanded = Vec::new();
+    let tokens = expand_items(items, macros, &mut expanded)?
+        .into_iter()
+        .map(|item| item.token)
+        .collect();
+
+    Ok((tokens, expanded))
+}
+
+fn expand_items(
+    items: Vec<Item>,
+    macros: &HashMap<String, Macro>,
+    expanded: &mut Vec<String>,
+) -> Result<Vec<Item>> {
+    let mut input = VecDeque::from(items);
+    let mut output = Vec::new();
+
+    while let Some(item) = input.pop_front() {
+        let name = &item.token.text;
+        let m = match macros.get(name) {
+            Some(m) if item.token.kind == TokenKind::Identifier && !item.hideset.contains(name) => {
+                m
+            }
+            _ => {
+                output.push(item);
+                continue;
+            }
+        };
+
+        let (args, mut hideset) = if m.is_function_like() {
+            // The name of a function-like macro which is not followed by an
+            // argument list is not an invocation.
+            if !input.front().is_some_and(|next| next.token.is_punct("(")) {
+                output.push(item);
+                continue;
+            }
+
+            let (args, rparen) = collect_args(&mut input, m, name)?;
+            let hideset = item
+                .hideset
+                .iter()
+                .filter(|name| rparen.hideset.contains(name))
+                .cloned()
+                .collect();
+
+            (args, hideset)
+        } else {
+            (Vec::new(), item.hideset.clone())
+        };
+        hideset.push(name.clone());
+        if !expanded.contains(name) {
+            expanded.push(name.clone());
+        }
+
+        let mut replacement = substitute(m, &args, macros, expanded)?;
+        for replaced in &mut replacement {
+            replaced.hideset.extend(hideset.iter().cloned());
+        }
+        if let Some(first) = replacement.first_mut() {
+            first.token.space_before = item.token.space_before;
+        }
+
+        // The replacement is rescanned along with the rest of the input.
+        for replaced in replacement.into_iter().rev() {
+            input.push_front(replaced);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Collect the arguments of a function-like macro invocation, returning them
+/// along with the closing parenthesis.
+fn collect_args(
+    input: &mut VecDeque<Item>,
+    m: &Macro,
+    name: &str,
+) -> Result<(Vec<Vec<Item>>, Item)> {
+    input.pop_front(); // '('
+
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    let rparen = loop {
+        let item = match input.pop_front() {
+            Some(item) => item,
+            None => bail!("Unterminated invocation of macro: {}", name),
+        };
+
+        if item.token.is_punct("(") {
+            depth += 1;
+        } else if item.token.is_punct(")") {
+            if depth == 0 {
+                break item;
+            }
+            depth -= 1;
+        } else if item.token.is_punct(",") && depth == 0 {
+            // Any further arguments belong to the variadic parameter.
+            let params = m.params.as_ref().unwrap();
+            if !(m.is_variadic() && args.len() == params.len()) {
+                args.push(Vec::new());
+                continue;
+            }
+        }
+
+        args.last_mut().unwrap().push(item);
+    };
+
+    let params = m.params.as_ref().unwrap();
+    if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+        args.clear();
+    } else if m.is_variadic() && args.len() == params.len() - 1 {
+        args.push(Vec::new());
+    }
+
+    if args.len() != params.len() {
+        bail!(
+            "Macro {} expects {} argument(s), but {} were given",
+            name,
+            params.len(),
+            args.len()
+        );
+    }
+
+    Ok((args, rparen))
+}
+
+/// Substitute the arguments into the replacement list of a macro, handling
+/// the `#` and `##` operators.
+fn substitute(
+    m: &Macro,
+    args: &[Vec<Item>],
+    macros: &HashMap<String, Macro>,
+    expanded: &mut Vec<String>,
+) -> Result<Vec<Item>> {
+    // Empty arguments adjacent to '##' are represented by placemarkers (`None`)
+    // until all pasting has been performed.
+    let mut output: Vec<Option<Item>> = Vec::new();
+    let body = &m.tokens;
+
+    let mut i = 0;
+    while i < body.len() {
+        let token = &body[i];
+        let next = body.get(i + 1);
+
+        if token.is_punct("#") && m.is_function_like() {
+            let index = match next.and_then(|next| m.param_index(next)) {
+                Some(index) => index,
+                None => bail!("'#' is not followed by a macro parameter"),
+            };
+
+            output.push(Some(Item {
+                token: stringify(&args[index], token.space_before),
+                hideset: Vec::new(),
+            }));
+            i += 2;
+        } else if token.is_punct("##") {
+            let rhs = next.unwrap();
+            let rhs = match m.param_index(rhs) {
+                Some(index) => args[index].clone(),
+                None => vec![plain(rhs)],
+            };
+
+            let lhs = output.pop().flatten();
+            let mut rhs = rhs.into_iter();
+            match (lhs, rhs.next()) {
+                (Some(lhs), Some(first)) => output.push(Some(paste(lhs, first)?)),
+                (lhs, first) => output.push(lhs.or(first)),
+            }
+            output.extend(rhs.map(Some));
+            i += 2;
+        } else if let Some(index) = m.param_index(token) {
+            let pasted = next.is_some_and(|next| next.is_punct("##"));
+            let arg = if pasted {
+                args[index].clone()
+            } else {
+                expand_items(args[index].clone(), macros, expanded)?
+            };
+
+            if arg.is_empty() && pasted {
+                output.push(None);
+            }
+            for (position, mut item) in arg.into_iter().enumerate() {
+                if position == 0 {
+                    item.token.space_before = token.space_before;
+                }
+                output.push(Some(item));
+            }
+            i += 1;
+        } else {
+            output.push(Some(plain(token)));
+            i += 1;
+        }
+    }
+
+    Ok(output.into_iter().flatten().collect())
+}
+
+fn plain(token: &Token) -> Item {
+    Item {
+        token: token.clone(),
+        hideset: Vec::new(),
+    }
+}
+
+/// Convert the tokens of a macro argument into a string literal.
+fn stringify(arg: &[Item], space_before: bool) -> Token {
+    let mut text = String::from("\"");
+    for (position, item) in arg.iter().enumerate() {
+        if position > 0 && item.token.space_before {
+            text.push(' ');
+        }
+
+        match item.token.kind {
+            TokenKind::String | TokenKind::Character => {
+                text.push_str(&item.token.text.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            _ => text.push_str(&item.token.text),
+        }
+    }
+    text.push('"');
+
+    Token {
+        kind: TokenKind::String,
+        text,
+        space_before,
+    }
+}
+
+/// Concatenate two tokens, which must result in a single valid token.
+fn paste(lhs: Item, rhs: Item) -> Result<Item> {
+    let text = format!("{}{}", lhs.token.text, rhs.token.text);
+    let mut tokens = lexer::tokenize(&text)?;
+    if tokens.len() != 1 {
+        bail!(
+            "Pasting '{}' and '{}' does not give a valid token",
+            lhs.token.text,
+            rhs.token.text
+        );
+    }
+
+    let mut token = tokens.remove(0);
+    token.space_before = lhs.token.space_before;
+
+    Ok(Item {
+        token,
+        hideset: lhs.hideset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Definition<'a> = (&'a str, Option<&'a [&'a str]>, &'a str);
+
+    /// Define macros from `(identifier, params, body)` triples.
+    fn macros(definitions: &[Definition]) -> HashMap<String, Macro> {
+        definitions
+            .iter()
+            .map(|(identifier, params, body)| {
+                let params = params.map(|params| params.iter().map(|p| p.to_string()).collect());
+                let m = Macro::new(params, body, false, None).unwrap();
+                (identifier.to_string(), m)
+            })
+            .collect()
+    }
+
+    fn expand_str(definitions: &[Definition], source: &str) -> Result<String> {
+        let tokens = expand(lexer::tokenize(source)?, &macros(definitions))?;
+        Ok(lexer::join(&tokens))
+    }
+
+    const CAT: Definition = ("CAT", Some(&["a", "b"]), "a ## b");
+
+    #[test]
+    fn pasted_tokens_are_rescanned() {
+        let definitions = [
+            CAT,
+            ("XCAT", Some(&["a", "b"][..]), "CAT(a, b)"),
+            ("X", None, "1"),
+            ("XY", None, "42"),
+        ];
+
+        // Arguments of '##' are not expanded, but the result is rescanned.
+        assert_eq!(expand_str(&definitions, "CAT(X,Y)").unwrap(), "42");
+        // An intermediate macro causes the arguments to be expanded first.
+        assert_eq!(expand_str(&definitions, "XCAT(X,Y)").unwrap(), "1Y");
+    }
+
+    #[test]
+    fn empty_arguments_adjacent_to_paste() {
+        let definitions = [CAT, ("X", None, "1")];
+
+        assert_eq!(expand_str(&definitions, "CAT(,Y)").unwrap(), "Y");
+        assert_eq!(expand_str(&definitions, "CAT(X,)").unwrap(), "1");
+        assert_eq!(expand_str(&definitions, "[CAT(,)]").unwrap(), "[]");
+    }
+
+    #[test]
+    fn stringify_escapes_literals() {
+        let definitions = [("STR", Some(&["x"][..]), "#x")];
+
+        assert_eq!(
+            expand_str(&definitions, r#"STR("a\"b"   c)"#).unwrap(),
+            r#""\"a\\\"b\" c""#
+        );
+        assert_eq!(expand_str(&definitions, "STR('\"')").unwrap(), r#""'\"'""#);
+    }
+
+    #[test]
+    fn self_referential_macros() {
+        let definitions = [
+            ("A", None, "A"),
+            ("B", None, "C + 1"),
+            ("C", None, "B"),
+            ("F", Some(&["x"][..]), "F(x) x"),
+        ];
+
+        assert_eq!(expand_str(&definitions, "A").unwrap(), "A");
+        assert_eq!(expand_str(&definitions, "B").unwrap(), "B + 1");
+        assert_eq!(expand_str(&definitions, "F(A)").unwrap(), "F(A) A");
+    }
+
+    #[test]
+    fn paste_at_either_end() {
+        assert!(Macro::new(None, "## x", false, None).is_err());
+        assert!(Macro::new(Some(vec!["x".to_string()]), "x ##", false, None).is_err());
+    }
+
+    #[test]
+    fn expanded_macros() {
+        let definitions = [
+            ("A", None, "B + EMPTY"),
+            ("B", None, "F(C)"),
+            ("C", None, "1"),
+            ("EMPTY", None, ""),
+            ("F", Some(&["x"][..]), "(x + C)"),
+        ];
+        let (tokens, expanded) =
+            expand_traced(lexer::tokenize("A").unwrap(), &macros(&definitions)).unwrap();
+        assert_eq!(lexer::join(&tokens), "(1 + 1) +");
+        assert_eq!(expanded, ["A", "B", "F", "C", "EMPTY"]);
+    }
+}
@@ -1,169 +1,359 @@
-use std::{collections::HashMap, env, fs, path::PathBuf, str::FromStr};
+use std::{collections::BTreeMap, path::PathBuf};
 
-use anyhow::Result;
-use enum_as_inner::EnumAsInner;
-use regex::Regex;
+use anyhow::{bail, Context, Result};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
 use strum::IntoEnumIterator;
-use strum_macros::{Display, EnumIter, EnumString};
-
-// Note that for the ESP32, since we are not using an RTOS we need to use the
-// 'xtensa_esp108' overlay instead of the 'xtensa_esp32' overlay.
-// https://docs.espressif.com/projects/esp-idf/en/v3.3.5/api-guides/jtag-debugging/tips-and-quirks.html
-#[derive(Debug, Clone, Copy, PartialEq, Display, EnumIter)]
-enum Chip {
-    #[strum(to_string = "xtensa_esp108")]
-    Esp32,
-    #[strum(to_string = "xtensa_esp32s2")]
-    Esp32s2,
-    #[strum(to_string = "xtensa_esp32s3")]
-    Esp32s3,
-    #[strum(to_string = "xtensa_lx106")]
-    Esp8266,
-}
+use xtensa_core_isa::{
+    generate,
+    Chip,
+    CoreConfig,
+    CoreFeatures,
+    CoreModels,
+    Diff,
+    Format,
+    InterruptTable,
+    MemoryMap,
+    VectorTable,
+};
 
-impl Chip {
-    fn core_isa_path(&self) -> Result<PathBuf> {
-        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("xtensa-overlays")
-            .join(self.to_string())
-            .join("newlib/newlib/libc/sys/xtensa/include/xtensa/config/core-isa.h")
-            .canonicalize()?;
+/// Parse the core-isa.h configuration headers of Xtensa-based Espressif chips.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Parse this header in place of the chip's header from the overlays. When
+    /// several chips are given, this may be repeated to give the header of
+    /// each chip in turn.
+    #[arg(long, global = true)]
+    header: Vec<PathBuf>,
 
-        Ok(path)
-    }
+    /// Fail if any definition cannot be resolved.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Print output in this format (json, toml or yaml) rather than as text.
+    #[arg(long, global = true, value_parser = parse_format)]
+    format: Option<Format>,
+
+    #[command(subcommand)]
+    command: Command,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
-enum InterruptType {
-    #[strum(serialize = "XTHAL_INTTYPE_EXTERN_EDGE")]
-    ExternEdge,
-    #[strum(serialize = "XTHAL_INTTYPE_EXTERN_LEVEL")]
-    ExternLevel,
-    #[strum(serialize = "XTHAL_INTTYPE_NMI")]
-    Nmi,
-    #[strum(serialize = "XTHAL_INTTYPE_PROFILING")]
-    Profiling,
-    #[strum(serialize = "XTHAL_INTTYPE_SOFTWARE")]
-    Software,
-    #[strum(serialize = "XTHAL_INTTYPE_TIMER")]
-    Timer,
-    #[strum(serialize = "XTHAL_TIMER_UNCONFIGURED")]
-    TimerUnconfigured,
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the supported chips, along with the name of each one's overlay.
+    ListChips,
+    /// Print every definition of a chip.
+    Dump {
+        chip: Chip,
+        /// Print where each definition was defined, along with its raw
+        /// replacement text.
+        #[arg(long, conflicts_with_all = ["models", "format"])]
+        locations: bool,
+        /// Print the typed models built from the definitions instead.
+        #[arg(long)]
+        models: bool,
+    },
+    /// Print the value of a single definition.
+    Get { chip: Chip, identifier: String },
+    /// Print the value of a single definition, along with the chain of macros
+    /// through which it was resolved.
+    Query { chip: Chip, identifier: String },
+    /// Print the definitions which differ between two chips, grouped by
+    /// category.
+    Diff {
+        first: Chip,
+        second: Chip,
+        /// Print the differences as Markdown tables.
+        #[arg(long, conflicts_with = "format")]
+        markdown: bool,
+    },
+    /// Generate source code for the given chips, or for every chip if none
+    /// are given.
+    Generate {
+        kind: GenerateKind,
+        chips: Vec<Chip>,
+        /// Name the linker region of a memory, as '<memory>=<name>' (such as
+        /// 'INSTRAM0=iram_seg').
+        #[arg(long, value_name = "MEMORY=NAME")]
+        name: Vec<String>,
+        /// Reserve bytes at the start of a memory, as '<memory>=<size>'.
+        #[arg(long, value_name = "MEMORY=SIZE")]
+        reserve_start: Vec<String>,
+        /// Reserve bytes at the end of a memory, as '<memory>=<size>'.
+        #[arg(long, value_name = "MEMORY=SIZE")]
+        reserve_end: Vec<String>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, EnumAsInner)]
-enum Value {
-    Integer(i64),
-    Interrupt(InterruptType),
-    String(String),
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GenerateKind {
+    /// A Rust module of interrupt constants.
+    Interrupts,
+    /// A GNU ld MEMORY block.
+    Memory,
+    /// A GNU as vector section skeleton.
+    Vectors,
+    /// An LLVM target feature string.
+    TargetFeatures,
+    /// A rustc target specification.
+    TargetJson,
 }
 
 fn main() -> Result<()> {
-    for chip in Chip::iter() {
-        println!("{}", chip);
-
-        // Parse the definitions for each chip from its 'core-isa.h' file. Note that for
-        // the ESP32 there is a single definition which requires special handling.
-        let mut config = parse_defines(chip)?;
-        if chip == Chip::Esp32 {
-            fix_esp32_xchal_use_memctl(&mut config);
+    let cli = Cli::parse();
+    cli.check_conflicts();
+
+    match &cli.command {
+        Command::ListChips => {
+            if let Some(format) = cli.format {
+                let chips = Chip::iter()
+                    .map(|chip| (chip.name(), chip.to_string()))
+                    .collect::<BTreeMap<_, _>>();
+                print!("{}", format.serialize(&chips)?);
+            } else {
+                for chip in Chip::iter() {
+                    println!("{} ({})", chip.name(), chip);
+                }
+            }
+        }
+        Command::Dump {
+            chip,
+            locations,
+            models,
+        } => {
+            let config = cli.configs(&[*chip])?.remove(0);
+            let models = models.then(|| CoreModels::from_defines(&config.to_map()));
+            for error in models.iter().flat_map(|models| &models.errors) {
+                eprintln!("{}", error);
+            }
+
+            match (cli.format, models) {
+                (Some(format), Some(models)) => print!("{}", format.serialize(&models)?),
+                (Some(format), None) => print!("{}", format.serialize(&config)?),
+                (None, Some(models)) => println!("{:#?}", models),
+                (None, None) => {
+                    for definition in config.iter() {
+                        if *locations {
+                            println!(
+                                "{}: {} = {} => {}",
+                                definition.location,
+                                definition.identifier,
+                                definition.raw,
+                                definition.value
+                            );
+                        } else {
+                            println!("{} = {}", definition.identifier, definition.value);
+                        }
+                    }
+                }
+            }
+        }
+        Command::Get { chip, identifier } => {
+            let config = cli.configs(&[*chip])?.remove(0);
+            let value = config
+                .value(identifier)
+                .with_context(|| format!("The {:?} has no definition of {}", chip, identifier))?;
+
+            if let Some(format) = cli.format {
+                print!(
+                    "{}",
+                    format.serialize(&BTreeMap::from([(identifier, value)]))?
+                );
+            } else {
+                println!("{}", value);
+            }
+        }
+        Command::Query { chip, identifier } => {
+            let config = cli.configs(&[*chip])?.remove(0);
+            let resolution = config.query(identifier)?;
+
+            if let Some(format) = cli.format {
+                print!("{}", format.serialize(&resolution)?);
+            } else {
+                println!("{} = {}", resolution.identifier, resolution.value);
+                for step in &resolution.trace {
+                    match &step.location {
+                        Some(location) => print!("  {}: ", location),
+                        None => print!("  <predefined>: "),
+                    }
+                    println!("{} = {}", step.identifier, step.raw);
+                }
+            }
+        }
+        Command::Diff {
+            first,
+            second,
+            markdown,
+        } => {
+            let configs = cli.configs(&[*first, *second])?;
+            let diff = Diff::new(&configs[0].to_map(), &configs[1].to_map());
+
+            if let Some(format) = cli.format {
+                print!("{}", format.serialize(&diff)?);
+            } else if *markdown {
+                print!("{}", diff.to_markdown(first.name(), second.name()));
+            } else {
+                print!("{}", diff);
+            }
         }
+        Command::Generate {
+            kind,
+            chips,
+            name,
+            reserve_start,
+            reserve_end,
+        } => {
+            let chips = if chips.is_empty() {
+                Chip::iter().collect()
+            } else {
+                chips.clone()
+            };
 
-        println!("\n{:#?}\n\n", config);
+            let mut linker_memory = generate::LinkerMemory::new();
+            for option in name {
+                let (memory, name) = assignment(option)?;
+                linker_memory = linker_memory.name(memory, name);
+            }
+            for option in reserve_start {
+                let (memory, size) = assignment(option)?;
+                linker_memory = linker_memory.reserve_start(memory, parse_size(size)?);
+            }
+            for option in reserve_end {
+                let (memory, size) = assignment(option)?;
+                linker_memory = linker_memory.reserve_end(memory, parse_size(size)?);
+            }
+
+            for (i, (chip, config)) in chips.iter().zip(cli.configs(&chips)?).enumerate() {
+                // Separate the generated sources of each chip by a blank line.
+                if i > 0 && *kind != GenerateKind::TargetFeatures {
+                    println!();
+                }
+
+                let defines = config.to_map();
+                match kind {
+                    GenerateKind::Interrupts => {
+                        let table = InterruptTable::from_defines(&defines)?;
+                        print!("{}", generate::rust_interrupts(*chip, &table));
+                    }
+                    GenerateKind::Memory => {
+                        let memory = MemoryMap::from_defines(&defines)?;
+                        print!("{}", linker_memory.generate(*chip, &memory)?);
+                    }
+                    GenerateKind::Vectors => {
+                        let table = VectorTable::from_defines(&defines)?;
+                        print!("{}", generate::assembly_vectors(*chip, &table)?);
+                    }
+                    GenerateKind::TargetFeatures => {
+                        let features = CoreFeatures::from_defines(&defines)?;
+                        println!("{}: {}", chip.name(), generate::target_features(&features));
+                    }
+                    GenerateKind::TargetJson => {
+                        let features = CoreFeatures::from_defines(&defines)?;
+                        print!("{}", generate::target_json(*chip, &features));
+                    }
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
-fn parse_defines(chip: Chip) -> Result<HashMap<String, Value>> {
-    let re_define = Regex::new(r"^#define[\s]+([a-zA-Z\d_]+)[\s]+([^\s]+)")?;
-    let re_ident = Regex::new(r"^[a-zA-Z\d_]+$")?;
-    let re_string = Regex::new(r#""([^"]+)""#)?;
-
-    // Iterate through each line containing a definition. Attempt to match the
-    // various components and map identifiers to values.
-    let mut map: HashMap<String, Value> = HashMap::new();
-    for define in find_all_defines(chip)? {
-        if !re_define.is_match(&define) {
-            println!("Define not matched: {}", define);
-            continue;
-        }
-
-        let captures = re_define.captures(&define).unwrap();
-        let identifier = captures.get(1).unwrap().as_str().to_string();
-        let value = captures.get(2).unwrap().as_str().to_string();
-
-        let value = if let Ok(integer) = value.parse::<i64>() {
-            // Decimal integer literal
-            Value::Integer(integer)
-        } else if let Ok(integer) = i64::from_str_radix(&value.replace("0x", ""), 16) {
-            // Hexadecimal integer literal
-            Value::Integer(integer)
-        } else if let Ok(interrupt) = InterruptType::from_str(&value) {
-            // Interrupt type
-            Value::Interrupt(interrupt)
-        } else if re_string.is_match(&value) {
-            // String
-            Value::String(value.replace("\"", ""))
-        } else if re_ident.is_match(&value) && map.contains_key(&value) {
-            // Identifier
-            map.get(&value).unwrap().to_owned()
-        } else {
-            // We will handle this particular case after, so no need to report it.
-            if chip != Chip::Esp32 && identifier != "XCHAL_USE_MEMCTL" {
-                println!("Unable to process definition: {} = {}", identifier, value);
+impl Cli {
+    /// Exit with a usage error if conflicting arguments were given which clap
+    /// does not catch itself. Conflicts with a global argument are only
+    /// detected by clap when the global argument follows the subcommand.
+    fn check_conflicts(&self) {
+        let conflict = match &self.command {
+            Command::Dump {
+                locations: true, ..
+            } if self.format.is_some() => {
+                Some("'--locations' cannot be used with '--format <FORMAT>'")
             }
-            continue;
+            Command::Diff { markdown: true, .. } if self.format.is_some() => {
+                Some("'--markdown' cannot be used with '--format <FORMAT>'")
+            }
+            Command::Generate {
+                kind,
+                name,
+                reserve_start,
+                reserve_end,
+                ..
+            } if *kind != GenerateKind::Memory => {
+                if !name.is_empty() {
+                    Some("'--name' can only be used when generating 'memory'")
+                } else if !reserve_start.is_empty() {
+                    Some("'--reserve-start' can only be used when generating 'memory'")
+                } else if !reserve_end.is_empty() {
+                    Some("'--reserve-end' can only be used when generating 'memory'")
+                } else {
+                    None
+                }
+            }
+            _ => None,
         };
 
-        map.insert(identifier, value);
+        if let Some(conflict) = conflict {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the argument {}", conflict),
+                )
+                .exit();
+        }
     }
 
-    Ok(map)
-}
+    /// Parse the header of each chip, which is either the corresponding
+    /// '--header' or the chip's header from the overlays, printing any
+    /// diagnostics to stderr.
+    fn configs(&self, chips: &[Chip]) -> Result<Vec<CoreConfig>> {
+        if self.header.len() > chips.len() {
+            bail!(
+                "{} headers were given, but only {} chips",
+                self.header.len(),
+                chips.len()
+            );
+        }
 
-fn find_all_defines(chip: Chip) -> Result<Vec<String>> {
-    let path = chip.core_isa_path()?;
-    let lines = fs::read_to_string(path)?
-        .lines()
-        .filter_map(|line| {
-            if line.starts_with("#define") {
-                Some(line.to_string())
-            } else {
-                None
+        let mut configs = Vec::new();
+        for (i, chip) in chips.iter().enumerate() {
+            let path = match self.header.get(i) {
+                Some(path) => path.clone(),
+                None => chip.core_isa_path()?,
+            };
+            let config = chip.parser().strict(self.strict).parse_file(path)?;
+
+            for diagnostic in config.diagnostics() {
+                eprintln!("{}", diagnostic);
             }
-        })
-        .collect::<Vec<_>>();
 
-    Ok(lines)
-}
+            configs.push(config);
+        }
 
-fn fix_esp32_xchal_use_memctl(map: &mut HashMap<String, Value>) {
-    // NOTE: the value of `use_memctl` should subsequently be AND'ed with
-    //       '(XCHAL_HW_MIN_VERSION >= XTENSA_HWVERSION_RE_2012_0)', however
-    //       the latter identifier is not defined anywhere.
-    macro_rules! to_integer {
-        ($identifier:expr) => {
-            map.get($identifier)
-                .unwrap()
-                .as_integer()
-                .unwrap()
-                .to_owned()
-        };
+        Ok(configs)
     }
+}
 
-    let loop_buffer_size = to_integer!("XCHAL_LOOP_BUFFER_SIZE");
-    let dcache_is_coherent = to_integer!("XCHAL_DCACHE_IS_COHERENT");
-    let have_icache_dyn_ways = to_integer!("XCHAL_HAVE_ICACHE_DYN_WAYS");
-    let have_dcache_dyn_ways = to_integer!("XCHAL_HAVE_DCACHE_DYN_WAYS");
+fn parse_format(format: &str) -> Result<Format> {
+    format
+        .parse()
+        .with_context(|| format!("Unknown format: {}", format))
+}
 
-    let use_memctl = (loop_buffer_size > 0)
-        || dcache_is_coherent != 0
-        || have_icache_dyn_ways != 0
-        || have_dcache_dyn_ways != 0;
+/// Split an option value of the form `<key>=<value>`.
+fn assignment(option: &str) -> Result<(&str, &str)> {
+    match option.split_once('=') {
+        Some(assignment) => Ok(assignment),
+        None => bail!("Expected <key>=<value>, found: {}", option),
+    }
+}
 
-    let identifier = String::from("XCHAL_USE_MEMCTL");
-    let value = Value::Integer(use_memctl as i64);
+/// Parse a size given in either decimal or hexadecimal (with a `0x` prefix).
+fn parse_size(size: &str) -> Result<u32> {
+    let result = match size.strip_prefix("0x").or_else(|| size.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => size.parse(),
+    };
 
-    map.insert(identifier, value);
+    result.with_context(|| format!("Invalid size: {}", size))
 }
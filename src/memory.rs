@@ -0,0 +1,209 @@
+//! A typed model of the local memories described by the `XCHAL_INSTROM*`,
+//! `XCHAL_INSTRAM*`, `XCHAL_DATAROM*`, `XCHAL_DATARAM*`, `XCHAL_URAM*` and
+//! `XCHAL_XLMI*` definitions.
+
+use std::{collections::HashMap, fmt, ops::Range};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+use crate::{integer, Value};
+
+/// The kind of a local memory, whose [Display] implementation yields the
+/// infix used by its definitions.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumIter, Serialize,
+)]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum MemoryKind {
+    InstRom,
+    InstRam,
+    DataRom,
+    DataRam,
+    /// Unified (instruction and data) RAM.
+    URam,
+    /// The Xtensa Local Memory Interface, a data port for memory-mapped
+    /// devices.
+    Xlmi,
+}
+
+/// The error protection of a local memory, as given by the
+/// `XCHAL_<kind><n>_ECC_PARITY` definitions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub enum EccParity {
+    #[default]
+    None,
+    Parity,
+    Ecc,
+}
+
+/// A single local memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct MemoryRegion {
+    pub kind: MemoryKind,
+    /// The index of the memory amongst those of the same kind.
+    pub index: u32,
+    /// The virtual address at which the memory starts.
+    pub vaddr: u32,
+    /// The physical address at which the memory starts.
+    pub paddr: u32,
+    /// The size of the memory, in bytes.
+    pub size: u32,
+    pub ecc_parity: EccParity,
+}
+
+impl MemoryRegion {
+    /// The range of virtual addresses covered by the memory.
+    pub fn range(&self) -> Range<u64> {
+        self.vaddr as u64..self.vaddr as u64 + self.size as u64
+    }
+
+    /// Whether the virtual address ranges of the memories overlap.
+    pub fn overlaps(&self, other: &MemoryRegion) -> bool {
+        let (a, b) = (self.range(), other.range());
+        a.start < b.end && b.start < a.end
+    }
+}
+
+impl fmt::Display for MemoryRegion {
+    /// Formats the region using the prefix of its definitions, such as
+    /// `DATARAM1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.kind, self.index)
+    }
+}
+
+/// All local memories of a chip, ordered by kind and then index.
+///
+/// ```no_run
+/// use xtensa_core_isa::{parse_defines, Chip, MemoryMap};
+///
+/// let memory = MemoryMap::from_defines(&parse_defines(Chip::Esp32)?)?;
+/// for region in &memory.regions {
+///     println!("{}: {:#010x} ({} bytes)", region, region.vaddr, region.size);
+/// }
+/// for (a, b) in memory.overlaps() {
+///     println!("{} overlaps {}", a, b);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MemoryMap {
+    pub regions: Vec<MemoryRegion>,
+}
+
+impl MemoryMap {
+    /// Assemble the memory map from the `XCHAL_NUM_<kind>` definitions, along
+    /// with the `XCHAL_<kind><n>_VADDR`, `_PADDR`, `_SIZE` and `_ECC_PARITY`
+    /// definitions of each memory. Kinds whose count is not defined are taken
+    /// to have no memories.
+    pub fn from_defines(defines: &HashMap<String, Value>) -> Result<Self> {
+        let mut regions = Vec::new();
+        for kind in MemoryKind::iter() {
+            let count = match defines.get(&format!("XCHAL_NUM_{}", kind)) {
+                Some(_) => integer(defines, &format!("XCHAL_NUM_{}", kind))?,
+                None => 0,
+            };
+
+            for index in 0..count as u32 {
+                let prefix = format!("XCHAL_{}{}", kind, index);
+                let ecc_parity = match defines.get(&format!("{}_ECC_PARITY", prefix)) {
+                    Some(_) => match integer(defines, &format!("{}_ECC_PARITY", prefix))? {
+                        0 => EccParity::None,
+                        1 => EccParity::Parity,
+                        2 => EccParity::Ecc,
+                        value => bail!("Unsupported value of {}_ECC_PARITY: {}", prefix, value),
+                    },
+                    None => EccParity::None,
+                };
+
+                regions.push(MemoryRegion {
+                    kind,
+                    index,
+                    vaddr: integer(defines, &format!("{}_VADDR", prefix))? as u32,
+                    paddr: integer(defines, &format!("{}_PADDR", prefix))? as u32,
+                    size: integer(defines, &format!("{}_SIZE", prefix))? as u32,
+                    ecc_parity,
+                });
+            }
+        }
+
+        Ok(Self { regions })
+    }
+
+    /// The memories of the given kind, ordered by index.
+    pub fn of_kind(&self, kind: MemoryKind) -> impl Iterator<Item = &MemoryRegion> {
+        self.regions
+            .iter()
+            .filter(move |region| region.kind == kind)
+    }
+
+    /// Every pair of memories whose virtual address ranges overlap.
+    pub fn overlaps(&self) -> Vec<(&MemoryRegion, &MemoryRegion)> {
+        let mut overlaps = Vec::new();
+        for (i, a) in self.regions.iter().enumerate() {
+            for b in &self.regions[i + 1..] {
+                if a.overlaps(b) {
+                    overlaps.push((a, b));
+                }
+            }
+        }
+
+        overlaps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    const HEADER: &str = "
+        #define XCHAL_NUM_INSTRAM 1
+        #define XCHAL_INSTRAM0_VADDR 0x40080000
+        #define XCHAL_INSTRAM0_PADDR 0x40080000
+        #define XCHAL_INSTRAM0_SIZE 0x20000
+        #define XCHAL_INSTRAM0_ECC_PARITY 2
+        #define XCHAL_NUM_DATARAM 2
+        #define XCHAL_DATARAM0_VADDR 0x3FFF0000
+        #define XCHAL_DATARAM0_PADDR 0x3FFF0000
+        #define XCHAL_DATARAM0_SIZE 0x10000
+        #define XCHAL_DATARAM1_VADDR 0x3FFE0000
+        #define XCHAL_DATARAM1_PADDR 0x3FFE0000
+        #define XCHAL_DATARAM1_SIZE 0x10000
+    ";
+
+    #[test]
+    fn regions() {
+        let map = MemoryMap::from_defines(&parse_str_defines(HEADER).unwrap()).unwrap();
+
+        let names = map
+            .regions
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["INSTRAM0", "DATARAM0", "DATARAM1"]);
+        assert_eq!(map.regions[0].ecc_parity, EccParity::Ecc);
+        assert_eq!(map.regions[1].ecc_parity, EccParity::None);
+        assert_eq!(map.of_kind(MemoryKind::DataRam).count(), 2);
+        assert_eq!(map.regions[0].range(), 0x4008_0000..0x400a_0000);
+
+        // Adjacent regions do not overlap.
+        assert!(map.overlaps().is_empty());
+    }
+
+    #[test]
+    fn overlapping_regions() {
+        let header = HEADER.replace("XCHAL_DATARAM1_SIZE 0x10000", "XCHAL_DATARAM1_SIZE 0x10001");
+        let map = MemoryMap::from_defines(&parse_str_defines(&header).unwrap()).unwrap();
+
+        let overlaps = map
+            .overlaps()
+            .into_iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(overlaps, [("DATARAM0".to_string(), "DATARAM1".to_string())]);
+    }
+}
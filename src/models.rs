@@ -0,0 +1,93 @@
+//! The typed models of a configuration, collected together.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{Caches, CoreFeatures, InterruptTable, MemoryMap, Value, VectorTable};
+
+/// Every typed model which can be built from a chip's definitions.
+///
+/// Models which cannot be built, for example because the definitions they
+/// rely on are missing, are `None` and are omitted when serialized. The
+/// reason each one could not be built is recorded in [CoreModels::errors].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CoreModels {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub features: Option<CoreFeatures>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interrupts: Option<InterruptTable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caches: Option<Caches>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryMap>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vectors: Option<VectorTable>,
+    /// Why each of the missing models could not be built.
+    #[serde(skip)]
+    pub errors: Vec<String>,
+}
+
+impl CoreModels {
+    pub fn from_defines(defines: &HashMap<String, Value>) -> Self {
+        let mut errors = Vec::new();
+
+        Self {
+            features: model(&mut errors, "features", CoreFeatures::from_defines(defines)),
+            interrupts: model(
+                &mut errors,
+                "interrupts",
+                InterruptTable::from_defines(defines),
+            ),
+            caches: model(&mut errors, "caches", Caches::from_defines(defines)),
+            memory: model(&mut errors, "memory", MemoryMap::from_defines(defines)),
+            vectors: model(&mut errors, "vectors", VectorTable::from_defines(defines)),
+            errors,
+        }
+    }
+}
+
+/// Take the model which was built, or record why it could not be.
+fn model<T>(errors: &mut Vec<String>, name: &str, result: Result<T>) -> Option<T> {
+    match result {
+        Ok(model) => Some(model),
+        Err(err) => {
+            errors.push(format!("Unable to build the {} model: {:#}", name, err));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    #[test]
+    fn errors() {
+        let defines = parse_str_defines(
+            "
+            #define XCHAL_HAVE_FP 1
+            #define XCHAL_NUM_INTERRUPTS 0
+            #define XCHAL_ICACHE_SIZE 1024
+            #define XCHAL_ICACHE_LINESIZE 32
+            #define XCHAL_ICACHE_WAYS 1
+            #define XCHAL_ICACHE_SETWIDTH 4
+            #define XCHAL_DCACHE_SIZE 0
+        ",
+        )
+        .unwrap();
+        let models = CoreModels::from_defines(&defines);
+
+        assert!(models.features.unwrap().fp);
+        assert_eq!(models.interrupts, Some(InterruptTable::default()));
+        assert_eq!(models.caches, None);
+        assert_eq!(
+            models.errors[0],
+            "Unable to build the caches model: Inconsistent ICACHE geometry: size is 1024, but 1 \
+             ways of 16 sets of 32 byte lines is 512"
+        );
+        assert_eq!(models.errors.len(), 1);
+    }
+}
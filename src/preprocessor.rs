@@ -0,0 +1,829 @@
+//! A minimal C preprocessor, sufficient for the `core-isa.h` headers.
+//!
+//! Conditional directives are tracked so that only definitions within active
+//! branches are collected, and `#include` directives are followed using the
+//! configured search directories.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    mem,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _, Result};
+use regex::Regex;
+
+use crate::{
+    diagnostics::{Diagnostic, DiagnosticKind},
+    expr::{self, Context},
+    lexer::{self, Token, TokenKind},
+    macros::{self, Macro},
+    Location,
+};
+
+/// The state of a single `#if`/`#ifdef`/`#ifndef` group.
+#[derive(Debug, Clone, Copy)]
+struct Conditional {
+    /// Whether the enclosing group is active.
+    parent_active: bool,
+    /// Whether the current branch of this group is active.
+    active: bool,
+    /// Whether any branch of this group has been taken.
+    taken: bool,
+    /// Whether the `#else` branch has been reached.
+    seen_else: bool,
+}
+
+pub(crate) struct Preprocessor {
+    macros: HashMap<String, Macro>,
+    order: Vec<String>,
+    conditionals: Vec<Conditional>,
+    /// The number of conditional groups which were open when the current file
+    /// was entered.
+    file_depth: usize,
+    include_dirs: Vec<PathBuf>,
+    /// The files currently being processed, innermost last.
+    include_stack: Vec<PathBuf>,
+    /// The include guard macros of previously processed files.
+    include_guards: HashMap<PathBuf, String>,
+    /// Files containing `#pragma once`.
+    include_once: HashSet<PathBuf>,
+    diagnostics: Vec<Diagnostic>,
+    re_directive: Regex,
+    re_define: Regex,
+    re_not_defined: Regex,
+}
+
+impl Preprocessor {
+    pub(crate) fn new(predefined: &[(String, String)], include_dirs: &[PathBuf]) -> Result<Self> {
+        let mut preprocessor = Self {
+            macros: HashMap::new(),
+            order: Vec::new(),
+            conditionals: Vec::new(),
+            file_depth: 0,
+            include_dirs: include_dirs.to_vec(),
+            include_stack: Vec::new(),
+            include_guards: HashMap::new(),
+            include_once: HashSet::new(),
+            diagnostics: Vec::new(),
+            re_directive: Regex::new(r"^\s*#\s*([a-z]+)\b\s*(.*)$")?,
+            re_define: Regex::new(r"^([a-zA-Z_][a-zA-Z\d_]*)(?:\(([^)]*)\))?\s*(.*)$")?,
+            re_not_defined: Regex::new(r"^!\s*defined\s*\(?\s*([a-zA-Z_][a-zA-Z\d_]*)")?,
+        };
+
+        for (identifier, body) in predefined {
+            preprocessor.define(identifier, Macro::new(None, body.trim(), true, None)?);
+        }
+
+        Ok(preprocessor)
+    }
+
+    /// Process the header located at `path`, as the main header being parsed.
+    ///
+    /// Unlike an included header, the main header is never skipped. Should its
+    /// include guard already be defined, such as by a header included from a
+    /// prelude, the guard is undefined so that its definitions are collected
+    /// regardless, and a diagnostic is reported.
+    pub(crate) fn process_file(&mut self, path: &Path, predefined: bool) -> Result<()> {
+        let path = canonicalize(path)?;
+        let source = read_header(&path)?;
+
+        if let Some(guard) = self.include_guard(&source) {
+            if let Some(previous) = self.macros.get(&guard) {
+                let reason = match &previous.location {
+                    Some(location) => format!(
+                        "Include guard of {} was already defined at {}",
+                        path.display(),
+                        location
+                    ),
+                    None => format!("Include guard of {} was already predefined", path.display()),
+                };
+
+                self.diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::DefinedIncludeGuard,
+                    identifier: guard.clone(),
+                    location: previous.location.clone(),
+                    reason,
+                });
+                self.undefine(&guard);
+            }
+        }
+
+        self.process_header(path, &source, predefined)
+    }
+
+    /// Process the header located at `path`, as named by an `#include`
+    /// directive.
+    ///
+    /// Headers whose include guard is already defined, or which contain
+    /// `#pragma once` and have already been processed, are skipped.
+    fn include_file(&mut self, path: &Path, predefined: bool) -> Result<()> {
+        let path = canonicalize(path)?;
+
+        if self.include_once.contains(&path) {
+            return Ok(());
+        }
+        if let Some(guard) = self.include_guards.get(&path) {
+            if self.macros.contains_key(guard) {
+                return Ok(());
+            }
+        }
+
+        let source = read_header(&path)?;
+        self.process_header(path, &source, predefined)
+    }
+
+    fn process_header(&mut self, path: PathBuf, source: &str, predefined: bool) -> Result<()> {
+        if self.include_stack.contains(&path) {
+            bail!("Recursive inclusion of header: {}", path.display());
+        }
+
+        if let Some(guard) = self.include_guard(source) {
+            self.include_guards.insert(path.clone(), guard);
+        }
+
+        self.include_stack.push(path.clone());
+        let result = self.process(source, Some(&path), predefined);
+        self.include_stack.pop();
+
+        result
+    }
+
+    /// Process the contents of a header, collecting the definitions found in
+    /// its active branches. The `path` of the header, if any, is used to
+    /// resolve quoted includes and in error messages.
+    ///
+    /// When `predefined` is set, the definitions are marked as predefined
+    /// rather than as belonging to the header.
+    pub(crate) fn process(
+        &mut self,
+        source: &str,
+        path: Option<&Path>,
+        predefined: bool,
+    ) -> Result<()> {
+        let depth = self.conditionals.len();
+        let parent_depth = mem::replace(&mut self.file_depth, depth);
+
+        for (line_number, line) in lexer::logical_lines(source) {
+            self.process_line(&line, path, line_number, predefined)
+                .with_context(|| match path {
+                    Some(path) => format!("Error at {}:{}: {}", path.display(), line_number, line),
+                    None => format!("Error on line {}: {}", line_number, line),
+                })?;
+        }
+
+        if self.conditionals.len() != depth {
+            bail!("Unterminated conditional directive at end of file");
+        }
+        self.file_depth = parent_depth;
+
+        Ok(())
+    }
+
+    /// The active definitions, in the order in which they were defined.
+    pub(crate) fn definitions(&self) -> impl Iterator<Item = (&str, &Macro)> {
+        self.order
+            .iter()
+            .map(|identifier| (identifier.as_str(), &self.macros[identifier]))
+    }
+
+    /// The diagnostics reported while processing.
+    pub(crate) fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Whether the macro is the include guard of a processed header.
+    pub(crate) fn is_include_guard(&self, identifier: &str) -> bool {
+        self.include_guards
+            .values()
+            .any(|guard| guard == identifier)
+    }
+
+    /// The macro with the given identifier, if it is currently defined.
+    pub(crate) fn get(&self, identifier: &str) -> Option<&Macro> {
+        self.macros.get(identifier)
+    }
+
+    /// Whether a macro with the given identifier is currently defined.
+    pub(crate) fn is_defined(&self, identifier: &str) -> bool {
+        self.macros.contains_key(identifier)
+    }
+
+    fn is_active(&self) -> bool {
+        self.conditionals.last().is_none_or(|cond| cond.active)
+    }
+
+    fn process_line(
+        &mut self,
+        line: &str,
+        path: Option<&Path>,
+        line_number: usize,
+        predefined: bool,
+    ) -> Result<()> {
+        let captures = match self.re_directive.captures(line) {
+            Some(captures) => captures,
+            None => return Ok(()),
+        };
+        let directive = captures.get(1).unwrap().as_str();
+        let rest = captures.get(2).unwrap().as_str().trim();
+
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let parent_active = self.is_active();
+                let active = parent_active && self.condition(directive, rest)?;
+
+                self.conditionals.push(Conditional {
+                    parent_active,
+                    active,
+                    taken: active,
+                    seen_else: false,
+                });
+            }
+            "elif" => {
+                let cond = *self.current_conditional(directive)?;
+                if cond.seen_else {
+                    bail!("#elif after #else");
+                }
+
+                let active = cond.parent_active && !cond.taken && self.condition("if", rest)?;
+                let cond = self.conditionals.last_mut().unwrap();
+                cond.active = active;
+                cond.taken |= active;
+            }
+            "else" => {
+                let cond = self.current_conditional(directive)?;
+                if cond.seen_else {
+                    bail!("#else after #else");
+                }
+
+                cond.active = cond.parent_active && !cond.taken;
+                cond.taken = true;
+                cond.seen_else = true;
+            }
+            "endif" => {
+                self.current_conditional(directive)?;
+                self.conditionals.pop();
+            }
+            _ if !self.is_active() => {}
+            "define" => {
+                let location = Location {
+                    path: path.map(Path::to_path_buf),
+                    line: line_number,
+                };
+
+                // A malformed definition is reported rather than failing, so that
+                // the definitions which follow it are still collected.
+                let captures = match self.re_define.captures(rest) {
+                    Some(captures) => captures,
+                    None => {
+                        self.diagnostics.push(Diagnostic {
+                            kind: DiagnosticKind::MalformedDefinition,
+                            identifier: rest
+                                .split_whitespace()
+                                .next()
+                                .unwrap_or_default()
+                                .to_string(),
+                            location: Some(location),
+                            reason: String::from("Malformed #define directive"),
+                        });
+                        return Ok(());
+                    }
+                };
+                let identifier = captures.get(1).unwrap().as_str();
+                let body = captures.get(3).unwrap().as_str();
+
+                let m = captures
+                    .get(2)
+                    .map(|m| parse_params(m.as_str()))
+                    .transpose()
+                    .and_then(|params| {
+                        Macro::new(params, body, predefined, Some(location.clone()))
+                    });
+
+                match m {
+                    Ok(m) => self.define(identifier, m),
+                    Err(err) => self.diagnostics.push(Diagnostic {
+                        kind: DiagnosticKind::MalformedDefinition,
+                        identifier: identifier.to_string(),
+                        location: Some(location),
+                        reason: format!("Malformed definition '{}': {:#}", body, err),
+                    }),
+                }
+            }
+            "undef" => self.undefine(rest),
+            "include" => {
+                let path = self.resolve_include(rest)?;
+                self.include_file(&path, predefined)?;
+            }
+            "pragma" if rest == "once" => {
+                if let Some(path) = self.include_stack.last() {
+                    self.include_once.insert(path.clone());
+                }
+            }
+            // Any other directives have no effect on the definitions.
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn current_conditional(&mut self, directive: &str) -> Result<&mut Conditional> {
+        // Conditional groups may not span multiple files.
+        if self.conditionals.len() <= self.file_depth {
+            bail!("#{} without matching #if", directive);
+        }
+
+        Ok(self.conditionals.last_mut().unwrap())
+    }
+
+    /// Find the header named by an `#include` directive.
+    ///
+    /// Headers named using quotes are first searched for relative to the
+    /// directory of the current header; all headers are then searched for in
+    /// the include directories, in order.
+    fn resolve_include(&self, rest: &str) -> Result<PathBuf> {
+        let (name, quoted) = if let Some(name) = rest.strip_prefix('"') {
+            (name.split('"').next(), true)
+        } else if let Some(name) = rest.strip_prefix('<') {
+            (name.split('>').next(), false)
+        } else {
+            (None, false)
+        };
+
+        let name = match name {
+            Some(name) if !name.is_empty() => name,
+            _ => bail!("Malformed #include directive"),
+        };
+
+        let current_dir = self
+            .include_stack
+            .last()
+            .and_then(|path| path.parent())
+            .filter(|_| quoted);
+
+        current_dir
+            .into_iter()
+            .chain(self.include_dirs.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .with_context(|| format!("Unable to find included header: {}", name))
+    }
+
+    /// Detect the include guard of a header: a macro which is tested by an
+    /// `#ifndef` (or `#if !defined`) directive which, together with its
+    /// matching `#endif`, encloses all other directives in the header.
+    fn include_guard(&self, source: &str) -> Option<String> {
+        let mut guard = None;
+        let mut depth = 0;
+        for (_, line) in lexer::logical_lines(source) {
+            let captures = match self.re_directive.captures(&line) {
+                Some(captures) => captures,
+                None => continue,
+            };
+            let directive = captures.get(1).unwrap().as_str();
+            let rest = captures.get(2).unwrap().as_str();
+
+            // Any directive following the group's `#endif` is not guarded.
+            if guard.is_some() && depth == 0 {
+                return None;
+            }
+
+            if guard.is_none() {
+                let identifier = match directive {
+                    "ifndef" => rest.split_whitespace().next(),
+                    "if" => self
+                        .re_not_defined
+                        .captures(rest)?
+                        .get(1)
+                        .map(|m| m.as_str()),
+                    _ => None,
+                };
+                guard = Some(identifier?.to_string());
+            }
+
+            match directive {
+                "if" | "ifdef" | "ifndef" => depth += 1,
+                "endif" => depth -= 1,
+                _ => {}
+            }
+        }
+
+        guard.filter(|_| depth == 0)
+    }
+
+    fn condition(&self, directive: &str, rest: &str) -> Result<bool> {
+        if directive == "if" {
+            let tokens = self.expand(rest)?;
+            return Ok(expr::evaluate(&lexer::join(&tokens), &Conditions)? != 0);
+        }
+
+        // The directive takes exactly one identifier.
+        let identifier = match lexer::tokenize(rest).ok().as_deref() {
+            Some([token]) if token.kind == TokenKind::Identifier => token.text.clone(),
+            _ => bail!("Malformed #{} directive", directive),
+        };
+
+        Ok(self.macros.contains_key(&identifier) == (directive == "ifdef"))
+    }
+
+    /// Fully expand the macros in `text`. Any `defined` operators are
+    /// evaluated beforehand, so that their operands are not expanded.
+    pub(crate) fn expand(&self, text: &str) -> Result<Vec<Token>> {
+        macros::expand(self.replace_defined(text)?, &self.macros)
+    }
+
+    /// Fully expand the macros in `text` as [Preprocessor::expand] does, also
+    /// returning the name of every macro which was expanded.
+    pub(crate) fn expand_traced(&self, text: &str) -> Result<(Vec<Token>, Vec<String>)> {
+        macros::expand_traced(self.replace_defined(text)?, &self.macros)
+    }
+
+    /// Tokenize `text`, replacing each `defined` operator with its result.
+    fn replace_defined(&self, text: &str) -> Result<Vec<Token>> {
+        let mut tokens = lexer::tokenize(text)?.into_iter();
+        let mut replaced = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            if !token.is_identifier("defined") {
+                replaced.push(token);
+                continue;
+            }
+
+            // Both 'defined X' and 'defined(X)' are valid.
+            let mut operand = tokens.next();
+            let parenthesized = operand.as_ref().is_some_and(|t| t.is_punct("("));
+            if parenthesized {
+                operand = tokens.next();
+            }
+
+            let identifier = match operand {
+                Some(operand) if operand.kind == TokenKind::Identifier => operand.text,
+                _ => bail!("Expected identifier after 'defined'"),
+            };
+            if parenthesized && !tokens.next().is_some_and(|t| t.is_punct(")")) {
+                bail!("Expected ')' after 'defined({}'", identifier);
+            }
+
+            replaced.push(Token {
+                kind: TokenKind::Number,
+                text: (self.is_defined(&identifier) as i64).to_string(),
+                space_before: token.space_before,
+            });
+        }
+
+        Ok(replaced)
+    }
+
+    fn define(&mut self, identifier: &str, m: Macro) {
+        match self.macros.get(identifier) {
+            None => self.order.push(identifier.to_string()),
+            Some(previous) if previous.params != m.params || previous.tokens != m.tokens => {
+                let reason = match &previous.location {
+                    Some(location) => format!("Redefined, previously defined at {}", location),
+                    None => String::from("Redefined, previously predefined"),
+                };
+
+                self.diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::Redefinition,
+                    identifier: identifier.to_string(),
+                    location: m.location.clone(),
+                    reason,
+                });
+            }
+            Some(_) => {}
+        }
+
+        self.macros.insert(identifier.to_string(), m);
+    }
+
+    fn undefine(&mut self, identifier: &str) {
+        self.macros.remove(identifier);
+        self.order.retain(|other| other != identifier);
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf> {
+    path.canonicalize()
+        .with_context(|| format!("Unable to find header: {}", path.display()))
+}
+
+fn read_header(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Unable to read header: {}", path.display()))
+}
+
+/// Parse the parameter list of a function-like macro definition. The variadic
+/// parameter, `...`, is named `__VA_ARGS__`.
+fn parse_params(params: &str) -> Result<Vec<String>> {
+    let params = params.trim();
+    if params.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let params: Vec<String> = params
+        .split(',')
+        .map(|param| match param.trim() {
+            "..." => "__VA_ARGS__".to_string(),
+            param => param.to_string(),
+        })
+        .collect();
+
+    for (position, param) in params.iter().enumerate() {
+        let valid = match param.as_str() {
+            "__VA_ARGS__" => position == params.len() - 1,
+            param => {
+                param.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && param.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+        };
+
+        if !valid {
+            bail!("Invalid macro parameter: {}", param);
+        }
+    }
+
+    Ok(params)
+}
+
+/// The context in which `#if` and `#elif` conditions are evaluated, once any
+/// macros have been expanded.
+///
+/// As in C, identifiers which are not defined as macros evaluate to zero.
+struct Conditions;
+
+impl Context for Conditions {
+    fn is_defined(&self, _identifier: &str) -> bool {
+        false
+    }
+
+    fn resolve(&self, _identifier: &str) -> Result<i64> {
+        Ok(0)
+    }
+
+    fn widen(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn preprocess(source: &str) -> Result<Preprocessor> {
+        let mut preprocessor = Preprocessor::new(&[], &[])?;
+        preprocessor.process(source, None, false)?;
+
+        Ok(preprocessor)
+    }
+
+    fn defined(source: &str) -> Vec<String> {
+        preprocess(source)
+            .unwrap()
+            .definitions()
+            .map(|(identifier, _)| identifier.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn nested_group_in_inactive_parent() {
+        let source = "
+            #if 0
+            #if 1
+            #define A 1
+            #else
+            #define B 1
+            #endif
+            #define C 1
+            #else
+            #define D 1
+            #endif
+        ";
+        assert_eq!(defined(source), ["D"]);
+    }
+
+    #[test]
+    fn elif_after_taken_branch() {
+        let source = "
+            #if 1
+            #define A 1
+            #elif 1
+            #define B 1
+            #else
+            #define C 1
+            #endif
+            #if 0
+            #elif 1
+            #define D 1
+            #elif 1
+            #define E 1
+            #endif
+        ";
+        assert_eq!(defined(source), ["A", "D"]);
+    }
+
+    #[test]
+    fn else_after_else() {
+        let err = preprocess("#if 0\n#else\n#else\n#endif").err().unwrap();
+        assert!(format!("{:#}", err).contains("#else after #else"));
+
+        let err = preprocess("#if 0\n#else\n#elif 1\n#endif").err().unwrap();
+        assert!(format!("{:#}", err).contains("#elif after #else"));
+    }
+
+    #[test]
+    fn unbalanced_endif() {
+        let err = preprocess("#define A 1\n#endif").err().unwrap();
+        assert!(format!("{:#}", err).contains("#endif without matching #if"));
+
+        let err = preprocess("#ifdef A\n#define B 1").err().unwrap();
+        assert!(format!("{:#}", err).contains("Unterminated conditional"));
+    }
+
+    #[test]
+    fn malformed_ifdef() {
+        for directive in [
+            "#ifdef",
+            "#ifndef  ",
+            "#ifdef A B",
+            "#ifndef A B",
+            "#ifdef 1",
+            "#ifdef A(",
+        ] {
+            let err = preprocess(&format!("{}\n#endif", directive)).err().unwrap();
+            assert!(format!("{:#}", err).contains("Malformed"), "{:#}", err);
+        }
+    }
+
+    #[test]
+    fn group_spanning_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("open.h"), "#if 1\n").unwrap();
+        fs::write(dir.path().join("close.h"), "#endif\n").unwrap();
+
+        let mut preprocessor = Preprocessor::new(&[], &[dir.path().to_path_buf()]).unwrap();
+        let err = preprocessor
+            .process("#include <open.h>\n#endif", None, false)
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", err).contains("Unterminated conditional"));
+
+        let mut preprocessor = Preprocessor::new(&[], &[dir.path().to_path_buf()]).unwrap();
+        let err = preprocessor
+            .process("#if 1\n#include <close.h>", None, false)
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", err).contains("#endif without matching #if"));
+    }
+
+    #[test]
+    fn recursive_include() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.h"), "#include \"b.h\"\n").unwrap();
+        fs::write(dir.path().join("b.h"), "#include \"a.h\"\n").unwrap();
+
+        let mut preprocessor = Preprocessor::new(&[], &[]).unwrap();
+        let err = preprocessor
+            .process_file(&dir.path().join("a.h"), false)
+            .err()
+            .unwrap();
+        assert!(
+            format!("{:#}", err).contains("Recursive inclusion of header"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn guarded_header_included_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("guarded.h"),
+            "#ifndef GUARDED_H\n#define GUARDED_H\n#define A 1\n#endif\n",
+        )
+        .unwrap();
+
+        let mut preprocessor = Preprocessor::new(&[], &[dir.path().to_path_buf()]).unwrap();
+        preprocessor
+            .process(
+                "#include <guarded.h>\n#undef A\n#include <guarded.h>",
+                None,
+                false,
+            )
+            .unwrap();
+        assert!(preprocessor.is_include_guard("GUARDED_H"));
+        assert!(!preprocessor.is_defined("A"));
+
+        // Once the guard is undefined the header is processed again.
+        preprocessor
+            .process("#undef GUARDED_H\n#include <guarded.h>", None, false)
+            .unwrap();
+        assert!(preprocessor.is_defined("A"));
+    }
+
+    #[test]
+    fn pragma_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("once.h"), "#pragma once\n#define A 1\n").unwrap();
+
+        let mut preprocessor = Preprocessor::new(&[], &[dir.path().to_path_buf()]).unwrap();
+        preprocessor
+            .process(
+                "#include <once.h>\n#undef A\n#include <once.h>",
+                None,
+                false,
+            )
+            .unwrap();
+        assert!(!preprocessor.is_defined("A"));
+    }
+
+    #[test]
+    fn quoted_include_relative_to_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let include_dir = dir.path().join("include");
+        let header_dir = dir.path().join("header");
+        fs::create_dir(&include_dir).unwrap();
+        fs::create_dir(&header_dir).unwrap();
+        fs::write(
+            include_dir.join("value.h"),
+            "#define QUOTED 1\n#define ANGLED 1\n",
+        )
+        .unwrap();
+        fs::write(
+            header_dir.join("value.h"),
+            "#define QUOTED 2\n#define ANGLED 2\n",
+        )
+        .unwrap();
+        fs::write(
+            header_dir.join("quoted.h"),
+            "#include \"value.h\"\n#undef ANGLED\n",
+        )
+        .unwrap();
+        fs::write(header_dir.join("angled.h"), "#include <value.h>\n").unwrap();
+
+        let mut preprocessor = Preprocessor::new(&[], &[include_dir]).unwrap();
+        preprocessor
+            .process_file(&header_dir.join("quoted.h"), false)
+            .unwrap();
+        assert_eq!(preprocessor.get("QUOTED").unwrap().body, "2");
+
+        preprocessor
+            .process_file(&header_dir.join("angled.h"), false)
+            .unwrap();
+        assert_eq!(preprocessor.get("ANGLED").unwrap().body, "1");
+    }
+
+    #[test]
+    fn main_header_guard_defined_by_prelude() {
+        let dir = tempfile::tempdir().unwrap();
+        let core_isa = dir.path().join("core-isa.h");
+        fs::write(
+            &core_isa,
+            "#ifndef CORE_ISA_H\n#define CORE_ISA_H\n#define A 1\n#endif\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("hal.h"), "#include \"core-isa.h\"\n").unwrap();
+
+        let mut preprocessor = Preprocessor::new(&[], &[dir.path().to_path_buf()]).unwrap();
+        preprocessor
+            .process("#include <hal.h>", None, true)
+            .unwrap();
+        preprocessor.process_file(&core_isa, false).unwrap();
+
+        let collected = preprocessor
+            .definitions()
+            .filter(|(_, m)| !m.predefined)
+            .map(|(identifier, _)| identifier)
+            .collect::<Vec<_>>();
+        assert_eq!(collected, ["A", "CORE_ISA_H"]);
+
+        let diagnostic = &preprocessor.diagnostics()[0];
+        assert_eq!(diagnostic.kind, DiagnosticKind::DefinedIncludeGuard);
+        assert_eq!(diagnostic.identifier, "CORE_ISA_H");
+    }
+
+    #[test]
+    fn malformed_definitions() {
+        let preprocessor = preprocess(
+            "#define A @\n#define B 2\n#define C ## x\n#define D(x) x ##\n#define E(x, 1) x\n#define F 3\n#define\n#define \u{e9} 1\n#define G 4",
+        )
+        .unwrap();
+
+        let defined = preprocessor
+            .definitions()
+            .map(|(identifier, _)| identifier)
+            .collect::<Vec<_>>();
+        assert_eq!(defined, ["B", "F", "G"]);
+
+        let malformed = preprocessor
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.kind, DiagnosticKind::MalformedDefinition);
+                diagnostic.identifier.as_str()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(malformed, ["A", "C", "D", "E", "", "\u{e9}"]);
+    }
+}
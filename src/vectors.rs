@@ -0,0 +1,341 @@
+//! A typed model of the reset, exception and interrupt vectors described by
+//! the `XCHAL_*_VECOFS`, `XCHAL_*_VECTOR_VADDR` and
+//! `XCHAL_RESET_VECTOR*_VADDR` definitions.
+
+use std::{collections::HashMap, fmt};
+
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+
+use crate::{flag, integer, Value};
+
+/// The kind of a vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VectorKind {
+    /// The reset vector selected by default, which is one of the primary and
+    /// alternate reset vectors.
+    Reset,
+    /// The primary reset vector, `XCHAL_RESET_VECTOR0_VADDR`.
+    PrimaryReset,
+    /// The alternate reset vector, `XCHAL_RESET_VECTOR1_VADDR`.
+    AlternateReset,
+    WindowOverflow4,
+    WindowUnderflow4,
+    WindowOverflow8,
+    WindowUnderflow8,
+    WindowOverflow12,
+    WindowUnderflow12,
+    /// The vector of a high-priority interrupt level, other than those of
+    /// the debug and NMI levels.
+    Level(u32),
+    Debug,
+    Nmi,
+    Kernel,
+    User,
+    DoubleException,
+    MemoryError,
+}
+
+impl VectorKind {
+    /// The infix of the vector's definitions, such as `INTLEVEL2` in
+    /// `XCHAL_INTLEVEL2_VECOFS`, or `RESET_VECTOR0` in
+    /// `XCHAL_RESET_VECTOR0_VADDR`.
+    fn infix(&self) -> String {
+        match self {
+            VectorKind::Reset => "RESET_VECTOR".to_string(),
+            VectorKind::PrimaryReset => "RESET_VECTOR0".to_string(),
+            VectorKind::AlternateReset => "RESET_VECTOR1".to_string(),
+            VectorKind::WindowOverflow4 => "WINDOW_OF4".to_string(),
+            VectorKind::WindowUnderflow4 => "WINDOW_UF4".to_string(),
+            VectorKind::WindowOverflow8 => "WINDOW_OF8".to_string(),
+            VectorKind::WindowUnderflow8 => "WINDOW_UF8".to_string(),
+            VectorKind::WindowOverflow12 => "WINDOW_OF12".to_string(),
+            VectorKind::WindowUnderflow12 => "WINDOW_UF12".to_string(),
+            VectorKind::Level(level) => format!("INTLEVEL{}", level),
+            VectorKind::Debug => "DEBUG".to_string(),
+            VectorKind::Nmi => "NMI".to_string(),
+            VectorKind::Kernel => "KERNEL".to_string(),
+            VectorKind::User => "USER".to_string(),
+            VectorKind::DoubleException => "DOUBLEEXC".to_string(),
+            VectorKind::MemoryError => "MEMERROR".to_string(),
+        }
+    }
+
+    /// Whether the vector is a reset vector, which has an address rather than
+    /// an offset from `VECBASE`.
+    fn is_reset(&self) -> bool {
+        matches!(
+            self,
+            VectorKind::Reset | VectorKind::PrimaryReset | VectorKind::AlternateReset
+        )
+    }
+
+    fn is_window(&self) -> bool {
+        matches!(
+            self,
+            VectorKind::WindowOverflow4
+                | VectorKind::WindowUnderflow4
+                | VectorKind::WindowOverflow8
+                | VectorKind::WindowUnderflow8
+                | VectorKind::WindowOverflow12
+                | VectorKind::WindowUnderflow12
+        )
+    }
+}
+
+impl fmt::Display for VectorKind {
+    /// Formats the kind as its variant name, with the level of
+    /// [VectorKind::Level] appended (such as `Level2`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VectorKind::Level(level) => write!(f, "Level{}", level),
+            kind => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+impl Serialize for VectorKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A single vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct Vector {
+    pub kind: VectorKind,
+    /// The offset of the vector from `VECBASE`, for all but the reset vector.
+    pub offset: Option<u32>,
+    /// The address of the vector when `VECBASE` holds its reset value.
+    pub vaddr: u32,
+    /// Whether the vector moves along with `VECBASE`, which is the case for
+    /// every vector with an offset if the chip has the relocatable vectors
+    /// option.
+    pub relocatable: bool,
+}
+
+/// All vectors of a chip.
+///
+/// ```no_run
+/// use xtensa_core_isa::{parse_defines, Chip, VectorTable};
+///
+/// let table = VectorTable::from_defines(&parse_defines(Chip::Esp32)?)?;
+/// for vector in table.relocatable() {
+///     println!("{:?}: {:#05x}", vector.kind, vector.offset.unwrap());
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct VectorTable {
+    /// The reset value of `VECBASE`, if the chip has the relocatable vectors
+    /// option.
+    pub vecbase: Option<u32>,
+    /// The vectors, ordered by kind.
+    pub vectors: Vec<Vector>,
+}
+
+impl VectorTable {
+    /// Assemble the table from the vector definitions.
+    ///
+    /// The window vectors are only included for chips with the windowed
+    /// register option, and the vectors of the debug and NMI interrupt levels
+    /// are given as [VectorKind::Debug] and [VectorKind::Nmi] rather than
+    /// [VectorKind::Level]. Other vectors are included if they are defined.
+    pub fn from_defines(defines: &HashMap<String, Value>) -> Result<Self> {
+        let vecbase = match flag(defines, "XCHAL_HAVE_VECBASE")? {
+            true => Some(integer(defines, "XCHAL_VECBASE_RESET_VADDR")? as u32),
+            false => None,
+        };
+
+        let mut kinds = vec![
+            VectorKind::Reset,
+            VectorKind::PrimaryReset,
+            VectorKind::AlternateReset,
+        ];
+        if flag(defines, "XCHAL_HAVE_WINDOWED")? {
+            kinds.extend([
+                VectorKind::WindowOverflow4,
+                VectorKind::WindowUnderflow4,
+                VectorKind::WindowOverflow8,
+                VectorKind::WindowUnderflow8,
+                VectorKind::WindowOverflow12,
+                VectorKind::WindowUnderflow12,
+            ]);
+        }
+
+        let debug_level = level(defines, "XCHAL_HAVE_DEBUG", "XCHAL_DEBUGLEVEL")?;
+        let nmi_level = level(defines, "XCHAL_HAVE_NMI", "XCHAL_NMILEVEL")?;
+        kinds.extend(
+            (2..=15)
+                .filter(|level| Some(*level) != debug_level && Some(*level) != nmi_level)
+                .map(VectorKind::Level),
+        );
+        kinds.extend([
+            VectorKind::Debug,
+            VectorKind::Nmi,
+            VectorKind::Kernel,
+            VectorKind::User,
+            VectorKind::DoubleException,
+            VectorKind::MemoryError,
+        ]);
+
+        let mut vectors = Vec::new();
+        for kind in kinds {
+            let infix = kind.infix();
+            let vector = if kind.is_reset() {
+                let identifier = format!("XCHAL_{}_VADDR", infix);
+                if !defines.contains_key(&identifier) {
+                    continue;
+                }
+
+                Vector {
+                    kind,
+                    offset: None,
+                    vaddr: integer(defines, &identifier)? as u32,
+                    relocatable: false,
+                }
+            } else {
+                let identifier = format!("XCHAL_{}_VECOFS", infix);
+                if !defines.contains_key(&identifier) {
+                    continue;
+                }
+
+                // The window vectors have no addresses of their own, only that
+                // of the block which contains them.
+                let offset = integer(defines, &identifier)? as u32;
+                let vaddr = if kind.is_window() {
+                    integer(defines, "XCHAL_WINDOW_VECTORS_VADDR")? as u32 + offset
+                } else {
+                    integer(defines, &format!("XCHAL_{}_VECTOR_VADDR", infix))? as u32
+                };
+
+                Vector {
+                    kind,
+                    offset: Some(offset),
+                    vaddr,
+                    relocatable: vecbase.is_some(),
+                }
+            };
+
+            vectors.push(vector);
+        }
+
+        Ok(Self { vecbase, vectors })
+    }
+
+    pub fn get(&self, kind: VectorKind) -> Option<&Vector> {
+        self.vectors.iter().find(|vector| vector.kind == kind)
+    }
+
+    /// The vectors which move along with `VECBASE`, ordered by offset.
+    pub fn relocatable(&self) -> Vec<&Vector> {
+        let mut vectors = self
+            .vectors
+            .iter()
+            .filter(|vector| vector.relocatable)
+            .collect::<Vec<_>>();
+        vectors.sort_by_key(|vector| vector.offset);
+
+        vectors
+    }
+}
+
+/// The interrupt level given by `identifier`, if the option `option` is
+/// present.
+fn level(defines: &HashMap<String, Value>, option: &str, identifier: &str) -> Result<Option<u32>> {
+    if flag(defines, option)? && defines.contains_key(identifier) {
+        Ok(Some(integer(defines, identifier)? as u32))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_defines;
+
+    const HEADER: &str = "
+        #define XCHAL_HAVE_VECBASE 1
+        #define XCHAL_VECBASE_RESET_VADDR 0x40000000
+        #define XCHAL_RESET_VECTOR0_VADDR 0x50000000
+        #define XCHAL_RESET_VECTOR1_VADDR 0x40000400
+        #define XCHAL_RESET_VECTOR_VADDR XCHAL_RESET_VECTOR1_VADDR
+        #define XCHAL_HAVE_DEBUG 1
+        #define XCHAL_DEBUGLEVEL 6
+        #define XCHAL_HAVE_NMI 1
+        #define XCHAL_NMILEVEL 7
+        #define XCHAL_INTLEVEL2_VECOFS 0x180
+        #define XCHAL_INTLEVEL2_VECTOR_VADDR 0x40000180
+        #define XCHAL_INTLEVEL6_VECOFS 0x300
+        #define XCHAL_INTLEVEL6_VECTOR_VADDR 0x40000300
+        #define XCHAL_INTLEVEL7_VECOFS 0x2C0
+        #define XCHAL_INTLEVEL7_VECTOR_VADDR 0x400002C0
+        #define XCHAL_DEBUG_VECOFS XCHAL_INTLEVEL6_VECOFS
+        #define XCHAL_DEBUG_VECTOR_VADDR XCHAL_INTLEVEL6_VECTOR_VADDR
+        #define XCHAL_NMI_VECOFS XCHAL_INTLEVEL7_VECOFS
+        #define XCHAL_NMI_VECTOR_VADDR XCHAL_INTLEVEL7_VECTOR_VADDR
+        #define XCHAL_WINDOW_OF4_VECOFS 0x000
+        #define XCHAL_WINDOW_VECTORS_VADDR 0x40000000
+    ";
+
+    fn kinds(table: &VectorTable) -> Vec<String> {
+        table
+            .vectors
+            .iter()
+            .map(|vector| vector.kind.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn debug_and_nmi_levels() {
+        let table = VectorTable::from_defines(&parse_str_defines(HEADER).unwrap()).unwrap();
+
+        // The debug and NMI levels are only present as their own vectors.
+        assert_eq!(
+            kinds(&table),
+            [
+                "Reset",
+                "PrimaryReset",
+                "AlternateReset",
+                "Level2",
+                "Debug",
+                "Nmi"
+            ]
+        );
+        assert_eq!(table.get(VectorKind::Debug).unwrap().offset, Some(0x300));
+        assert_eq!(table.get(VectorKind::Nmi).unwrap().vaddr, 0x4000_02c0);
+        assert!(!table.get(VectorKind::Reset).unwrap().relocatable);
+        assert_eq!(table.get(VectorKind::Reset).unwrap().vaddr, 0x4000_0400);
+        assert_eq!(
+            table.get(VectorKind::PrimaryReset).unwrap().vaddr,
+            0x5000_0000
+        );
+        assert_eq!(
+            table.get(VectorKind::AlternateReset).unwrap().vaddr,
+            0x4000_0400
+        );
+
+        let offsets = table
+            .relocatable()
+            .iter()
+            .map(|vector| vector.offset.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0x180, 0x2c0, 0x300]);
+    }
+
+    #[test]
+    fn windowed() {
+        let header = format!("{}\n#define XCHAL_HAVE_WINDOWED 1", HEADER);
+        let table = VectorTable::from_defines(&parse_str_defines(&header).unwrap()).unwrap();
+
+        assert_eq!(
+            kinds(&table)[2..5],
+            ["AlternateReset", "WindowOverflow4", "Level2"]
+        );
+        assert_eq!(
+            table.get(VectorKind::WindowOverflow4).unwrap().vaddr,
+            0x4000_0000
+        );
+    }
+}
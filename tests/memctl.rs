@@ -0,0 +1,78 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use xtensa_core_isa::{Chip, Value};
+
+/// The value of `XCHAL_USE_MEMCTL` as it was previously computed by hand for
+/// the ESP32, which does not take the hardware version into account.
+fn legacy_use_memctl(map: &HashMap<String, Value>) -> i64 {
+    let integer = |identifier: &str| *map[identifier].as_integer().unwrap();
+
+    let use_memctl = (integer("XCHAL_LOOP_BUFFER_SIZE") > 0)
+        || integer("XCHAL_DCACHE_IS_COHERENT") != 0
+        || integer("XCHAL_HAVE_ICACHE_DYN_WAYS") != 0
+        || integer("XCHAL_HAVE_DCACHE_DYN_WAYS") != 0;
+
+    use_memctl as i64
+}
+
+#[test]
+fn esp32_use_memctl_matches_legacy_value() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/esp32-core-isa.h");
+    let map = Chip::Esp32.parser().parse_file(path).unwrap().to_map();
+
+    assert_eq!(
+        map["XCHAL_USE_MEMCTL"],
+        Value::Integer(legacy_use_memctl(&map))
+    );
+}
+
+#[test]
+fn esp32_use_memctl_traces_hardware_version() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/esp32-core-isa.h");
+    let config = Chip::Esp32.parser().parse_file(path).unwrap();
+    let resolution = config.query("XCHAL_USE_MEMCTL").unwrap();
+
+    let step = resolution
+        .trace
+        .iter()
+        .find(|step| step.identifier == "XTENSA_HWVERSION_RE_2012_0")
+        .expect("XTENSA_HWVERSION_RE_2012_0 is not in the trace");
+    assert_eq!(step.raw, "250000");
+
+    let location = step.location.as_ref().unwrap();
+    assert!(location
+        .path
+        .as_ref()
+        .unwrap()
+        .ends_with("include/xtensa/hal.h"));
+
+    let identifiers = resolution
+        .trace
+        .iter()
+        .map(|step| step.identifier.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        identifiers[..3],
+        [
+            "XCHAL_USE_MEMCTL",
+            "XCHAL_LOOP_BUFFER_SIZE",
+            "XCHAL_DCACHE_IS_COHERENT"
+        ]
+    );
+    assert!(identifiers.contains(&"XCHAL_HW_MIN_VERSION"));
+}
+
+#[test]
+#[ignore = "requires the xtensa-overlays submodule"]
+fn overlay_use_memctl_matches_legacy_value() {
+    // The legacy computation only ever applied to the ESP32.
+    Chip::Esp32
+        .core_isa_path()
+        .expect("The xtensa-overlays submodule must be checked out");
+
+    let map = xtensa_core_isa::parse_defines(Chip::Esp32).unwrap();
+    assert_eq!(
+        map["XCHAL_USE_MEMCTL"],
+        Value::Integer(legacy_use_memctl(&map))
+    );
+}